@@ -0,0 +1,109 @@
+use std::{thread, time::Duration};
+
+use channel_protocol::channel_actor;
+use channel_protocol_runtime::{ClientError, Pending};
+
+struct CounterApp {
+    counter: i32,
+    prev_counter: i32,
+}
+
+/// The `&mut self` methods are the protocol, no trait to keep in sync with them.
+#[channel_actor(batch)]
+impl CounterApp {
+    pub const fn new() -> Self {
+        Self {
+            counter: 0,
+            prev_counter: 0,
+        }
+    }
+
+    /// Returns the current value, then adds `i` to the counter.
+    fn get_and_inc(&mut self, i: i32) -> i32 {
+        let val = self.counter;
+        self.inc(i);
+        val
+    }
+
+    fn inc(&mut self, i: i32) {
+        self.counter += i;
+    }
+
+    fn reset(&mut self) {
+        self.counter = 0;
+    }
+
+    fn get(&mut self) -> i32 {
+        self.counter
+    }
+
+    /// Like `get`, but the client gives up if the actor takes more than 500ms to reply.
+    #[timeout(ms = 500)]
+    fn peek(&mut self) -> i32 {
+        self.counter
+    }
+
+    fn has_changed(&self) -> bool {
+        self.counter != self.prev_counter
+    }
+
+    #[channel_actor(skip)]
+    const fn save_previous(&mut self) {
+        self.prev_counter = self.counter;
+    }
+}
+
+fn main() {
+    let (client, rx) = CounterAppClient::new();
+    thread::spawn(move || {
+        let mut app = CounterApp::new();
+        for message in rx {
+            println!("{message:?}");
+            app.save_previous();
+            app.dispatch(message);
+            if app.has_changed() {
+                println!("counter changed to {}", app.counter);
+            }
+        }
+    });
+
+    assert_eq!(0, client.get());
+    client.inc(2);
+    assert_eq!(2, client.get_and_inc(3));
+    assert_eq!(5, client.get());
+    client.reset();
+    assert_eq!(0, client.get());
+    assert_eq!(Ok(0), client.peek());
+
+    // Deferred calls are all sent before any reply is collected.
+    let pending = (1..=3)
+        .map(|i| client.get_and_inc_deferred(i))
+        .collect::<Vec<_>>();
+    let replies = pending
+        .into_iter()
+        .map(Pending::wait)
+        .collect::<Result<Vec<_>, _>>();
+    assert_eq!(Ok(vec![0, 1, 3]), replies);
+    let pending = client.get_deferred();
+    assert_eq!(Ok(6), pending.wait_timeout(Duration::from_secs(1)));
+
+    // A batch is a single message, no call from another client can run in between.
+    let value = client.batch(|batch| {
+        batch.reset();
+        batch.inc(5);
+        batch.get()
+    });
+    assert_eq!(Ok(5), value.wait());
+
+    // Nothing handles the messages of this client, calls with a timeout give up.
+    let (client, _rx) = CounterAppClient::new();
+    let client = client.with_timeout(Duration::from_millis(10));
+    assert_eq!(Err(ClientError::Timeout), client.get());
+    assert_eq!(Err(ClientError::Timeout), client.peek());
+
+    // `try_*` methods report a gone actor instead of panicking.
+    let (client, rx) = CounterAppClient::new();
+    drop(rx);
+    assert_eq!(Err(ClientError::Disconnected), client.try_inc(1));
+    assert_eq!(Err(ClientError::Disconnected), client.try_get());
+}
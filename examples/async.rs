@@ -1,85 +1,96 @@
-// use std::{thread, time::Duration};
+use std::{collections::HashMap, thread, time::Duration};
 
-// use tokio::spawn;
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::Dispatch;
 
-// pub enum Message {
-//     Start { label: String },
-//     Stop,
-//     GetTotalMessageCount(oneshot::Sender<usize>),
-// }
+/// Every client method is `async`, the handler runs in a tokio task.
+#[channel_protocol(tokio)]
+trait MessageProtocol {
+    fn start(label: impl Into<String>);
+    fn stop();
+    fn get_total_message_count() -> usize;
+}
 
-// #[derive(Clone)]
-// pub struct MessageSender(tokio::sync::mpsc::Sender<Message>);
+#[derive(Default)]
+struct MessageCounter {
+    count: usize,
+}
 
-// impl MessageSender {
-//     pub async fn start<T: Into<String>>(&self, label: T) {
-//         self.0
-//             .send(Message::Start {
-//                 label: label.into(),
-//             })
-//             .await
-//             .unwrap();
-//     }
+impl HandleMessageProtocolAsync for MessageCounter {
+    async fn start(&mut self, label: String) {
+        println!("start: {label}");
+        self.count += 1;
+    }
 
-//     pub async fn get_total_message_count(&self) -> usize {
-//         let (tx, rx) = oneshot::channel();
-//         self.0
-//             .send(Message::GetTotalMessageCount(tx))
-//             .await
-//             .unwrap();
-//         rx.await.unwrap()
-//     }
+    async fn stop(&mut self) {
+        println!("stop");
+        self.count += 1;
+    }
 
-//     pub async fn stop(&self) {
-//         self.0.send(Message::Stop).await.unwrap();
-//     }
-// }
+    async fn get_total_message_count(&mut self) -> usize {
+        self.count += 1;
+        self.count
+    }
+}
 
-// trait MessageProtocol {
-//     fn start(label: String);
-//     fn stop();
-//     fn get_total_message_count() -> usize;
-// }
+/// Handled by a std thread, async callers use the `*_async` methods to not block the executor.
+#[channel_protocol]
+trait SettingsProtocol {
+    fn set(key: impl Into<String>, value: u32);
+    fn get(key: impl Into<String>) -> Option<u32>;
+}
 
-// #[tokio::main]
-// async fn main() {
-//     let (tx, mut rx) = tokio::sync::mpsc::channel::<Message>(100);
-//     let context = MessageSender(tx);
-//     let other_context = context.clone();
+#[derive(Default)]
+struct Settings(HashMap<String, u32>);
 
-//     spawn(async move {
-//         loop {
-//             dbg!(thread::current().name());
-//             context.start("with label").await;
-//             tokio::time::sleep(Duration::from_secs(1)).await;
-//             context.stop().await;
-//             tokio::time::sleep(Duration::from_secs(1)).await;
-//             println!("{}", context.get_total_message_count().await);
-//         }
-//     });
+impl HandleSettingsProtocol for Settings {
+    fn set(&mut self, key: String, value: u32) {
+        self.0.insert(key, value);
+    }
 
-//     spawn(async move {
-//         loop {
-//             dbg!(thread::current().name());
-//             other_context.start("with label 2").await;
-//             tokio::time::sleep(Duration::from_secs(1)).await;
-//             other_context.stop().await;
-//             tokio::time::sleep(Duration::from_secs(1)).await;
-//             println!("{}", other_context.get_total_message_count().await);
-//         }
-//     });
+    fn get(&mut self, key: String) -> Option<u32> {
+        self.0.get(&key).copied()
+    }
+}
 
-//     let mut i = 0;
-//     while let Some(message) = rx.recv().await {
-//         match message {
-//             Message::Start { label } => println!("start: {label}"),
-//             Message::Stop => println!("stop"),
-//             Message::GetTotalMessageCount(sender) => {
-//                 sender.send(i).unwrap();
-//             }
-//         }
-//         i += 1;
-//     }
-// }
+#[tokio::main]
+async fn main() {
+    let (settings, settings_rx) = SettingsProtocolClient::new();
+    let settings_thread = thread::spawn(move || {
+        let mut settings = Settings::default();
+        for message in settings_rx {
+            settings.dispatch(message);
+        }
+    });
+    settings.set("repeat", 3);
 
-fn main() {}
+    let (client, handler) = MessageProtocolClient::spawn(MessageCounter::default());
+
+    let tasks = ["with label", "with label 2"].map(|label| {
+        let client = client.clone();
+        let settings = settings.clone();
+        tokio::spawn(async move {
+            let repeat = settings.get_async("repeat").await.unwrap_or(1);
+            for _ in 0..repeat {
+                client.start(label).await;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                client.stop().await;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                println!("{}", client.get_total_message_count().await);
+            }
+        })
+    });
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // The handler task ends once the last client is gone.
+    drop(client);
+    let counter = handler.await.unwrap();
+    println!("handled {} messages", counter.count);
+
+    // The same handler thread serves blocking callers.
+    println!("repeat: {:?}", settings.get("repeat"));
+    drop(settings);
+    settings_thread.join().unwrap();
+}
@@ -0,0 +1,94 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::{Backpressure, ClientError, Dispatch, MessageSender, SendError};
+
+/// Clients of this protocol send straight into a crossbeam channel.
+#[channel_protocol(sender = crossbeam_channel::Sender)]
+trait Counter {
+    fn inc(i: i32);
+    fn get() -> i32;
+}
+
+/// The same protocol over flume, a bounded channel rejecting calls when it is full.
+#[channel_protocol(sender = flume::Sender, backpressure = error)]
+trait Events {
+    fn key(code: u32);
+}
+
+/// Any type implementing `MessageSender` works, this one counts the messages it forwards.
+struct CountingSender<T> {
+    sender: mpsc::Sender<T>,
+    count: Arc<AtomicUsize>,
+}
+
+impl<T> Clone for CountingSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            count: self.count.clone(),
+        }
+    }
+}
+
+impl<T> MessageSender<T> for CountingSender<T> {
+    fn send(&self, message: T, _backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        Ok(self.sender.send(message)?)
+    }
+}
+
+#[channel_protocol(sender = CountingSender)]
+trait Logging {
+    fn log(message: impl Into<String>);
+}
+
+struct CounterState(i32);
+
+impl HandleCounter for CounterState {
+    fn inc(&mut self, i: i32) {
+        self.0 += i;
+    }
+
+    fn get(&mut self) -> i32 {
+        self.0
+    }
+}
+
+fn main() {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    let client = CounterClient::from_sender(sender);
+    thread::spawn(move || {
+        let mut counter = CounterState(0);
+        for message in receiver {
+            counter.dispatch(message);
+        }
+    });
+    client.inc(2);
+    client.inc(3);
+    assert_eq!(5, client.get());
+
+    let (sender, receiver) = flume::bounded(1);
+    let client = EventsClient::from_sender(sender);
+    client.key(1);
+    assert_eq!(Err(ClientError::Full), client.try_key(2));
+    assert_eq!(1, receiver.len());
+
+    let (sender, receiver) = mpsc::channel();
+    let count = Arc::default();
+    let client = LoggingClient::from_sender(CountingSender {
+        sender,
+        count: Arc::clone(&count),
+    });
+    client.log("hello");
+    client.log("world");
+    assert_eq!(2, receiver.try_iter().count());
+    assert_eq!(2, count.load(Ordering::Relaxed));
+}
@@ -0,0 +1,74 @@
+use std::thread;
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::Dispatch;
+
+#[channel_protocol]
+trait Logging {
+    fn log(message: impl Into<String>);
+    fn logs() -> Vec<String>;
+}
+
+#[channel_protocol]
+trait Counter<T> {
+    fn add(value: T);
+    fn total() -> T;
+}
+
+/// Composes `Logging` and `Counter<i64>`, every method of both is available on `AppClient`.
+#[channel_protocol]
+trait App: Logging + Counter<i64> {
+    fn reset();
+}
+
+#[derive(Default)]
+struct AppState {
+    logs: Vec<String>,
+    total: i64,
+}
+
+impl HandleLogging for AppState {
+    fn log(&mut self, message: String) {
+        self.logs.push(message);
+    }
+
+    fn logs(&mut self) -> Vec<String> {
+        self.logs.clone()
+    }
+}
+
+impl HandleCounter<i64> for AppState {
+    fn add(&mut self, value: i64) {
+        self.total += value;
+    }
+
+    fn total(&mut self) -> i64 {
+        self.total
+    }
+}
+
+impl HandleApp for AppState {
+    fn reset(&mut self) {
+        self.logs.push("reset".to_owned());
+        self.total = 0;
+    }
+}
+
+fn main() {
+    let (client, rx) = AppClient::new();
+    thread::spawn(move || {
+        let mut state = AppState::default();
+        for message in rx {
+            println!("{message:?}");
+            state.dispatch(message);
+        }
+    });
+
+    client.log("start");
+    client.add(2);
+    client.add(3);
+    assert_eq!(5, client.total());
+    client.reset();
+    assert_eq!(0, client.total());
+    assert_eq!(vec!["start", "reset"], client.logs());
+}
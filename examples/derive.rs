@@ -0,0 +1,64 @@
+use std::thread;
+
+use channel_protocol::ChannelProtocol;
+use channel_protocol_runtime::Dispatch;
+
+/// A hand-written message enum, the client and handler traits are derived from it.
+#[derive(ChannelProtocol, Debug)]
+pub enum Message {
+    Start {
+        label: String,
+    },
+    Stop,
+    GetTotalMessageCount(oneshot::Sender<usize>),
+    /// Named fields can hold the reply sender anywhere.
+    Rename {
+        label: String,
+        reply: oneshot::Sender<Option<String>>,
+    },
+}
+
+#[derive(Default)]
+struct Recorder {
+    label: Option<String>,
+    message_count: usize,
+}
+
+impl HandleMessage for Recorder {
+    fn start(&mut self, label: String) {
+        self.label = Some(label);
+    }
+
+    fn stop(&mut self) {
+        self.label = None;
+    }
+
+    fn get_total_message_count(&mut self) -> usize {
+        self.message_count
+    }
+
+    fn rename(&mut self, label: String) -> Option<String> {
+        self.label.replace(label)
+    }
+}
+
+fn main() {
+    let (client, rx) = MessageClient::new();
+    thread::spawn(move || {
+        let mut recorder = Recorder::default();
+        for message in rx {
+            println!("{message:?}");
+            recorder.message_count += 1;
+            recorder.dispatch(message);
+        }
+    });
+
+    client.start("with label".to_owned());
+    assert_eq!(
+        Some("with label".to_owned()),
+        client.rename("renamed".to_owned())
+    );
+    client.stop();
+    assert_eq!(None, client.rename("restarted".to_owned()));
+    assert_eq!(5, client.get_total_message_count());
+}
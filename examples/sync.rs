@@ -1,13 +1,18 @@
 use std::{fmt::Debug, sync::mpsc::Receiver, thread, time::Duration};
 
 use channel_protocol::channel_protocol;
+use channel_protocol_runtime::Dispatch;
 
 #[channel_protocol]
 trait CounterInputProtocol {
+    /// Returns the current value, then adds `i` to the counter.
     fn get_and_inc(i: i32) -> i32;
     fn inc_and_mul(add: i32, mul: i32) -> i32;
-    fn inc(i: i32);
-    fn dec(i: i32);
+    fn inc(&mut self, i: i32);
+    /// Handlers get this implementation unless they override it.
+    fn dec(&mut self, i: i32) {
+        self.inc(-i);
+    }
     fn reset();
     fn get() -> i32;
 }
@@ -40,10 +45,6 @@ impl HandleCounterInputProtocol for CounterApp {
         self.counter += i;
     }
 
-    fn dec(&mut self, i: i32) {
-        self.counter -= i;
-    }
-
     fn reset(&mut self) {
         self.counter = 0;
     }
@@ -51,11 +52,6 @@ impl HandleCounterInputProtocol for CounterApp {
     fn get(&mut self) -> i32 {
         self.counter
     }
-
-    fn dispatch(&mut self, message: CounterInputProtocolMessage) {
-        println!("{message:?}");
-        self._dispatch(message);
-    }
 }
 
 impl CounterApp {
@@ -89,6 +85,7 @@ fn manager_thread(
 ) {
     let mut app = CounterApp::new();
     for message in rx {
+        println!("{message:?}");
         app.save_previous();
         app.dispatch(message);
 
@@ -1,11 +1,12 @@
 use std::{
     fmt::Debug,
-    sync::mpsc::Receiver,
+    sync::mpsc,
     thread::{self, JoinHandle},
     time::Duration,
 };
 
 use channel_protocol::channel_protocol;
+use channel_protocol_runtime::{Dispatch, bounded};
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalSize,
@@ -16,17 +17,20 @@ use winit::{
     window::{Window, WindowAttributes},
 };
 
-#[channel_protocol]
+/// Sent straight to the event loop, the messages arrive in `ApplicationHandler::user_event`.
+#[channel_protocol(sender = winit::event_loop::EventLoopProxy)]
 trait WinitInputProtocol {
-    fn create_window(title: String, width: u32, height: u32);
+    fn create_window(title: impl Into<String>, width: u32, height: u32);
     fn is_window_open() -> bool;
     fn close_window();
-    fn set_title(title: String);
+    fn set_title(title: impl Into<String>);
     fn resize(width: u32, height: u32);
     fn teardown();
 }
 
-#[channel_protocol]
+/// Events are produced as fast as the user types, a slow consumer loses the oldest ones instead
+/// of letting the queue grow.
+#[channel_protocol(backpressure = drop_oldest)]
 trait WinitOutputProtocol {
     fn on_window_resized(width: u32, height: u32);
     fn on_key_event(key: KeyCode, is_pressed: bool);
@@ -138,24 +142,18 @@ impl ApplicationHandler<WinitInputProtocolMessage> for WinitApp {
 fn launch_winit_on_other_thread() -> (
     JoinHandle<()>,
     WinitInputProtocolClient,
-    Receiver<WinitOutputProtocolMessage>,
+    bounded::Receiver<WinitOutputProtocolMessage>,
 ) {
-    let (input_client, input_rx) = WinitInputProtocolClient::new();
-    let (output_client, output_rx) = WinitOutputProtocolClient::new();
+    let (output_client, output_rx) = WinitOutputProtocolClient::bounded(64);
+    let (proxy_tx, proxy_rx) = mpsc::channel();
 
-    let winit_thread = thread::spawn(|| {
+    let winit_thread = thread::spawn(move || {
         let event_loop = EventLoop::with_user_event()
             .with_any_thread(true)
             .build()
             .unwrap();
 
-        let event_sender = event_loop.create_proxy();
-
-        thread::spawn(move || {
-            for input_event in input_rx {
-                event_sender.send_event(input_event).unwrap();
-            }
-        });
+        proxy_tx.send(event_loop.create_proxy()).unwrap();
 
         event_loop
             .run_app(&mut WinitApp {
@@ -164,6 +162,7 @@ fn launch_winit_on_other_thread() -> (
             })
             .unwrap();
     });
+    let input_client = WinitInputProtocolClient::from_sender(proxy_rx.recv().unwrap());
 
     (winit_thread, input_client, output_rx)
 }
@@ -176,7 +175,7 @@ fn main() {
 
     let (winit_thread, winit_client, output_rx) = launch_winit_on_other_thread();
 
-    winit_client.create_window("Test window".into(), 600, 600);
+    winit_client.create_window("Test window", 600, 600);
 
     let mut width = 600;
     let mut height = 600;
@@ -206,14 +205,14 @@ fn main() {
                                 "Closing current window and respawning a new one 2 secs later"
                             );
                             winit_client.close_window();
-                            let winit_client_clone = winit_client.clone();
+                            // A weak client does not keep the app alive past its teardown.
+                            let weak_client = winit_client.downgrade();
                             thread::spawn(move || {
                                 thread::sleep(Duration::from_secs(2));
-                                winit_client_clone.create_window(
-                                    "Respawed window".into(),
-                                    600,
-                                    600,
-                                );
+                                if let Some(winit_client) = weak_client.upgrade() {
+                                    let _ =
+                                        winit_client.try_create_window("Respawed window", 600, 600);
+                                }
                             });
                         }
                         KeyCode::ArrowLeft => {
@@ -231,7 +230,7 @@ fn main() {
                         KeyCode::Enter => match mode {
                             Mode::Normal => {
                                 mode = Mode::TitleEditing(String::new());
-                                winit_client.set_title("Editing title...".into());
+                                winit_client.set_title("Editing title...");
                             }
                             Mode::TitleEditing(new_title) => {
                                 mode = Mode::Normal;
@@ -254,5 +253,6 @@ fn main() {
         }
     }
 
+    drop(winit_client);
     winit_thread.join().unwrap();
 }
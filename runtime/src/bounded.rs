@@ -0,0 +1,389 @@
+//! Bounded channel behind the `bounded` client constructors, applying a [`Backpressure`] policy
+//! when its queue is full.
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard,
+        mpsc::{RecvError, RecvTimeoutError, TryRecvError},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::SendError;
+
+/// What a send does when the queue of a bounded channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Wait until the handler makes room.
+    #[default]
+    Block,
+    /// Fail with [`SendError::Full`].
+    Error,
+    /// Discard the message being sent.
+    DropNewest,
+    /// Discard the oldest queued message to make room for the one being sent.
+    ///
+    /// Only the [`bounded`](crate::bounded) channel can evict a queued message. The other
+    /// bounded senders, such as `SyncSender`, crossbeam and flume, fail with
+    /// [`SendError::Full`] instead.
+    DropOldest,
+}
+
+/// Creates a channel holding at most `capacity` messages, sends apply `backpressure` when it is
+/// full.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+pub fn channel<T>(capacity: usize, backpressure: Backpressure) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a bounded channel needs a positive capacity");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            senders: 1,
+            receiver: true,
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        capacity,
+        backpressure,
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    backpressure: Backpressure,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver: bool,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // The queue stays consistent even if a thread panicked while holding the lock.
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Sending half of a bounded channel.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `message`, applying `backpressure` if given or the policy of the channel otherwise
+    /// when it is full.
+    pub fn send(&self, message: T, backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+        if !state.receiver {
+            return Err(SendError::Disconnected(message));
+        }
+        if state.queue.len() >= self.shared.capacity {
+            match backpressure.unwrap_or(self.shared.backpressure) {
+                Backpressure::Block => {
+                    state = self
+                        .shared
+                        .not_full
+                        .wait_while(state, |state| {
+                            state.receiver && state.queue.len() >= self.shared.capacity
+                        })
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    if !state.receiver {
+                        return Err(SendError::Disconnected(message));
+                    }
+                }
+                Backpressure::Error => return Err(SendError::Full(message)),
+                Backpressure::DropNewest => return Ok(()),
+                Backpressure::DropOldest => {
+                    state.queue.pop_front();
+                }
+            }
+        }
+        state.queue.push_back(message);
+        drop(state);
+        self.shared.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Whether the receiver has been dropped.
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().receiver
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+/// Receiving half of a bounded channel, with the interface of [`std::sync::mpsc::Receiver`].
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Blocks until a message arrives, fails once every sender is gone and the queue is empty.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self
+            .shared
+            .not_empty
+            .wait_while(self.shared.lock(), |state| {
+                state.queue.is_empty() && state.senders > 0
+            })
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let message = state.queue.pop_front().ok_or(RecvError)?;
+        drop(state);
+        self.shared.not_full.notify_one();
+        Ok(message)
+    }
+
+    /// Returns a message if one is queued.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock();
+        match state.queue.pop_front() {
+            Some(message) => {
+                drop(state);
+                self.shared.not_full.notify_one();
+                Ok(message)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Blocks until a message arrives or `timeout` elapses.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(message) = state.queue.pop_front() {
+                drop(state);
+                self.shared.not_full.notify_one();
+                return Ok(message);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    /// Iterates over the messages until every sender is gone.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    /// Iterates over the messages already queued.
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.try_recv().ok())
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receiver = false;
+        // Pending calls get their reply dropped instead of waiting forever.
+        let queue = std::mem::take(&mut state.queue);
+        drop(state);
+        self.shared.not_full.notify_all();
+        drop(queue);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// Iterator over the messages of a [`Receiver`], see [`Receiver::iter`].
+#[derive(Debug)]
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Owning iterator over the messages of a [`Receiver`].
+#[derive(Debug)]
+pub struct IntoIter<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { receiver: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn error_rejects_when_full() {
+        let (sender, receiver) = channel(1, Backpressure::Error);
+        sender.send(1, None).unwrap();
+        assert!(matches!(sender.send(2, None), Err(SendError::Full(2))));
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn drop_newest_discards_the_sent_message() {
+        let (sender, receiver) = channel(1, Backpressure::DropNewest);
+        sender.send(1, None).unwrap();
+        sender.send(2, None).unwrap();
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn drop_oldest_evicts_the_queued_message() {
+        let (sender, receiver) = channel(2, Backpressure::DropOldest);
+        for message in 1..=3 {
+            sender.send(message, None).unwrap();
+        }
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn send_policy_overrides_the_channel_one() {
+        let (sender, _receiver) = channel(1, Backpressure::Block);
+        sender.send(1, None).unwrap();
+        assert!(matches!(
+            sender.send(2, Some(Backpressure::Error)),
+            Err(SendError::Full(2))
+        ));
+    }
+
+    #[test]
+    fn block_waits_for_recv() {
+        let (sender, receiver) = channel(1, Backpressure::Block);
+        sender.send(1, None).unwrap();
+        let blocked = thread::spawn(move || sender.send(2, None));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!blocked.is_finished());
+
+        assert_eq!(receiver.recv(), Ok(1));
+        assert!(blocked.join().unwrap().is_ok());
+        assert_eq!(receiver.recv(), Ok(2));
+    }
+
+    #[test]
+    fn is_closed_once_the_receiver_is_dropped() {
+        let (sender, receiver) = channel::<i32>(1, Backpressure::Block);
+        assert!(!sender.is_closed());
+        drop(receiver);
+        assert!(sender.is_closed());
+    }
+
+    #[test]
+    fn dropping_the_receiver_unblocks_senders() {
+        let (sender, receiver) = channel(1, Backpressure::Block);
+        sender.send(1, None).unwrap();
+        let blocked = thread::spawn(move || sender.send(2, None));
+        thread::sleep(Duration::from_millis(50));
+        drop(receiver);
+
+        assert!(matches!(
+            blocked.join().unwrap(),
+            Err(SendError::Disconnected(2))
+        ));
+    }
+
+    #[test]
+    fn recv_ends_after_the_last_sender() {
+        let (sender, receiver) = channel(4, Backpressure::Block);
+        let other = sender.clone();
+        sender.send(1, None).unwrap();
+        other.send(2, None).unwrap();
+        drop(sender);
+        drop(other);
+
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(receiver.recv(), Err(RecvError));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn recv_timeout_times_out_on_an_empty_channel() {
+        let (_sender, receiver) = channel::<i32>(1, Backpressure::Block);
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+    }
+}
@@ -0,0 +1,52 @@
+/// Message enum of a protocol, handled by `H` through the stateless handler trait of the
+/// protocol.
+///
+/// The generated message enums implement it for every implementation of their handler, so
+/// that [`Dispatch::dispatch`] accepts the messages of every protocol a type handles.
+pub trait HandledBy<H: ?Sized> {
+    /// Calls the method of `handler` matching the message and sends back its reply.
+    fn dispatch_to(self, handler: &mut H);
+
+    /// Like [`Self::dispatch_to`], but calls `reply_dropped` when a caller stopped waiting for
+    /// its reply. Composed protocols forward the messages of their supertraits with their own
+    /// `reply_dropped`.
+    #[doc(hidden)]
+    fn dispatch_reporting(self, handler: &mut H, reply_dropped: fn(&mut H, &'static str));
+}
+
+/// Message enum of a protocol, handled by `H` through the stateful handler trait of the
+/// protocol with a state of type `S`.
+pub trait HandledWithStateBy<H: ?Sized, S> {
+    /// Calls the method of `handler` matching the message with `state` and sends back its
+    /// reply.
+    fn dispatch_with_state_to(self, handler: &mut H, state: S);
+
+    /// Like [`Self::dispatch_with_state_to`], but calls `reply_dropped` when a caller stopped
+    /// waiting for its reply.
+    #[doc(hidden)]
+    fn dispatch_with_state_reporting(
+        self,
+        handler: &mut H,
+        state: S,
+        reply_dropped: fn(&mut H, &'static str),
+    );
+}
+
+/// Entry point of the handlers, implemented for every type.
+///
+/// A single trait dispatches the messages of every protocol, so that a handler of a composed
+/// protocol, which also implements the handlers of its supertraits, still calls
+/// `handler.dispatch(message)` without naming the trait.
+pub trait Dispatch {
+    /// Calls the method of `self` matching `message` and sends back its reply.
+    fn dispatch<M: HandledBy<Self>>(&mut self, message: M) {
+        message.dispatch_to(self);
+    }
+
+    /// Calls the method of `self` matching `message` with `state` and sends back its reply.
+    fn dispatch_with_state<M: HandledWithStateBy<Self, S>, S>(&mut self, message: M, state: S) {
+        message.dispatch_with_state_to(self, state);
+    }
+}
+
+impl<H: ?Sized> Dispatch for H {}
@@ -0,0 +1,147 @@
+use std::{error::Error, fmt};
+
+/// Error returned by client methods whose protocol method returns a `Result`.
+///
+/// It merges the error returned by the handler with the ways the call itself can fail, so that
+/// callers can handle every failure mode through one `?`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError<E> {
+    /// The handler returned an error.
+    Failed(E),
+    /// The handler side of the channel is gone, the call could not be sent.
+    Disconnected,
+    /// The handler dropped the call without replying, typically because it panicked while
+    /// handling it.
+    Panicked,
+    /// The handler did not reply before the timeout of the call.
+    Timeout,
+    /// The bounded channel of the handler is full and its backpressure policy rejects the call.
+    Full,
+}
+
+impl<E: fmt::Display> fmt::Display for CallError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(error) => error.fmt(f),
+            Self::Disconnected => write!(f, "the handler is disconnected"),
+            Self::Panicked => write!(f, "the handler dropped the call without replying"),
+            Self::Timeout => write!(f, "the handler did not reply in time"),
+            Self::Full => write!(f, "the channel of the handler is full"),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for CallError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Failed(error) => Some(error),
+            Self::Disconnected | Self::Panicked | Self::Timeout | Self::Full => None,
+        }
+    }
+}
+
+impl<E> From<ClientError> for CallError<E> {
+    fn from(error: ClientError) -> Self {
+        match error {
+            ClientError::Disconnected => Self::Disconnected,
+            ClientError::ReplyDropped => Self::Panicked,
+            ClientError::Timeout => Self::Timeout,
+            ClientError::Full => Self::Full,
+        }
+    }
+}
+
+impl<T, E> From<SendError<T>> for CallError<E> {
+    fn from(error: SendError<T>) -> Self {
+        ClientError::from(error).into()
+    }
+}
+
+/// Error returned by the `try_*` client methods and by calls with a timeout when the call could
+/// not complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientError {
+    /// The handler side of the channel is gone, the call could not be sent.
+    Disconnected,
+    /// The handler dropped the call without replying, typically because it panicked while
+    /// handling it.
+    ReplyDropped,
+    /// The handler did not reply before the timeout of the call.
+    Timeout,
+    /// The bounded channel of the handler is full and its backpressure policy rejects the call.
+    Full,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disconnected => write!(f, "the handler is disconnected"),
+            Self::ReplyDropped => write!(f, "the handler dropped the call without replying"),
+            Self::Timeout => write!(f, "the handler did not reply in time"),
+            Self::Full => write!(f, "the channel of the handler is full"),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl<T> From<SendError<T>> for ClientError {
+    fn from(error: SendError<T>) -> Self {
+        match error {
+            SendError::Disconnected(_) => Self::Disconnected,
+            SendError::Full(_) => Self::Full,
+        }
+    }
+}
+
+/// Error returned when a message could not be sent, giving the message back.
+#[derive(Clone, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The receiving side of the channel is gone.
+    Disconnected(T),
+    /// The bounded channel is full and its backpressure policy rejects the message.
+    Full(T),
+}
+
+impl<T> SendError<T> {
+    /// The message that could not be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Disconnected(message) | Self::Full(message) => message,
+        }
+    }
+
+    /// Same error, for `f(message)`.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> SendError<U> {
+        match self {
+            Self::Disconnected(message) => SendError::Disconnected(f(message)),
+            Self::Full(message) => SendError::Full(f(message)),
+        }
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disconnected(_) => f.write_str("Disconnected(..)"),
+            Self::Full(_) => f.write_str("Full(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+            Self::Full(_) => write!(f, "sending on a full channel"),
+        }
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+impl<T> From<std::sync::mpsc::SendError<T>> for SendError<T> {
+    fn from(error: std::sync::mpsc::SendError<T>) -> Self {
+        Self::Disconnected(error.0)
+    }
+}
@@ -0,0 +1,18 @@
+//! # Channel Protocol Runtime
+//! Types used by the code generated by the [`channel-protocol`](https://docs.rs/channel-protocol)
+//! macro.
+pub mod bounded;
+mod dispatch;
+mod error;
+mod pending;
+mod sender;
+
+pub use bounded::Backpressure;
+pub use dispatch::{Dispatch, HandledBy, HandledWithStateBy};
+pub use error::{CallError, ClientError, SendError};
+pub use pending::Pending;
+pub use sender::{ClientSender, MessageSender};
+
+/// Used by the clients of `#[channel_protocol(tokio)]` protocols.
+#[cfg(feature = "tokio")]
+pub use tokio;
@@ -0,0 +1,73 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use crate::ClientError;
+
+/// Reply of a call sent by a `*_deferred` client method, to be collected later.
+///
+/// Several calls can be in flight at once, each handle receiving the reply of its own call. The
+/// handle is also a future resolving to the reply, so that async code can await it.
+#[derive(Debug)]
+pub struct Pending<T> {
+    /// Why the call could not be sent, if it could not.
+    receiver: Result<oneshot::Receiver<T>, ClientError>,
+}
+
+impl<T> Pending<T> {
+    /// Handle waiting for the reply on `receiver`, used by the generated code.
+    pub fn new(receiver: oneshot::Receiver<T>) -> Self {
+        Self {
+            receiver: Ok(receiver),
+        }
+    }
+
+    /// Handle of a call that could not be sent, used by the generated code.
+    pub fn failed(error: ClientError) -> Self {
+        Self {
+            receiver: Err(error),
+        }
+    }
+
+    /// Blocks until the reply arrives.
+    pub fn wait(self) -> Result<T, ClientError> {
+        self.receiver?.recv().map_err(|_| ClientError::ReplyDropped)
+    }
+
+    /// Returns the reply if it already arrived, `None` otherwise. The reply is only returned
+    /// once, later calls report [`ClientError::ReplyDropped`].
+    pub fn try_recv(&self) -> Result<Option<T>, ClientError> {
+        let receiver = self.receiver.as_ref().map_err(|error| *error)?;
+        match receiver.try_recv() {
+            Ok(reply) => Ok(Some(reply)),
+            Err(oneshot::TryRecvError::Empty) => Ok(None),
+            Err(oneshot::TryRecvError::Disconnected) => Err(ClientError::ReplyDropped),
+        }
+    }
+
+    /// Blocks until the reply arrives, or returns [`ClientError::Timeout`] after `timeout`. The
+    /// handle can still be waited on after a timeout.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<T, ClientError> {
+        let receiver = self.receiver.as_ref().map_err(|error| *error)?;
+        receiver.recv_timeout(timeout).map_err(|error| match error {
+            oneshot::RecvTimeoutError::Timeout => ClientError::Timeout,
+            oneshot::RecvTimeoutError::Disconnected => ClientError::ReplyDropped,
+        })
+    }
+}
+
+impl<T> Future for Pending<T> {
+    type Output = Result<T, ClientError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.get_mut().receiver {
+            Ok(receiver) => Pin::new(receiver)
+                .poll(cx)
+                .map_err(|_| ClientError::ReplyDropped),
+            Err(error) => Poll::Ready(Err(*error)),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::mpsc;
+
+use crate::{Backpressure, SendError, bounded};
+
+/// Sending side of a channel, as used by the generated clients. `backpressure` is the policy of
+/// the protocol method, if it has one, applied when a bounded channel is full, see
+/// [`Backpressure::DropOldest`] for the senders that cannot evict a queued message.
+///
+/// Implemented for the std senders, the [`bounded`] channel and [`ClientSender`], behind the
+/// `crossbeam` and `flume` features for the senders of these crates, behind the `winit` feature
+/// for `EventLoopProxy` and behind the `tokio` feature for `UnboundedSender`. Another sender type
+/// can be used by a protocol with `#[channel_protocol(sender = path::to::Sender)]` once it
+/// implements this trait.
+pub trait MessageSender<T>: Clone {
+    fn send(&self, message: T, backpressure: Option<Backpressure>) -> Result<(), SendError<T>>;
+
+    /// Whether the receiver is known to be gone. Senders that cannot tell return `false`, their
+    /// sends fail once it is.
+    fn is_closed(&self) -> bool {
+        false
+    }
+}
+
+/// Sending side of the clients created by `new` and `bounded`, an unbounded std channel or a
+/// bounded one.
+#[derive(Debug)]
+pub enum ClientSender<T> {
+    /// Channel of the `new` constructors.
+    Unbounded(mpsc::Sender<T>),
+    /// Channel of the `bounded` constructors.
+    Bounded(bounded::Sender<T>),
+}
+
+impl<T> MessageSender<T> for ClientSender<T> {
+    fn send(&self, message: T, backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        match self {
+            Self::Unbounded(sender) => MessageSender::send(sender, message, backpressure),
+            Self::Bounded(sender) => sender.send(message, backpressure),
+        }
+    }
+
+    /// Only known for the bounded channel, the std sender cannot tell.
+    fn is_closed(&self) -> bool {
+        match self {
+            Self::Unbounded(sender) => MessageSender::is_closed(sender),
+            Self::Bounded(sender) => sender.is_closed(),
+        }
+    }
+}
+
+impl<T> Clone for ClientSender<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Unbounded(sender) => Self::Unbounded(sender.clone()),
+            Self::Bounded(sender) => Self::Bounded(sender.clone()),
+        }
+    }
+}
+
+impl<T> From<mpsc::Sender<T>> for ClientSender<T> {
+    fn from(sender: mpsc::Sender<T>) -> Self {
+        Self::Unbounded(sender)
+    }
+}
+
+impl<T> From<bounded::Sender<T>> for ClientSender<T> {
+    fn from(sender: bounded::Sender<T>) -> Self {
+        Self::Bounded(sender)
+    }
+}
+
+impl<T> MessageSender<T> for bounded::Sender<T> {
+    fn send(&self, message: T, backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        Self::send(self, message, backpressure)
+    }
+
+    fn is_closed(&self) -> bool {
+        Self::is_closed(self)
+    }
+}
+
+/// Unbounded, never full.
+impl<T> MessageSender<T> for mpsc::Sender<T> {
+    fn send(&self, message: T, _backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        Ok(Self::send(self, message)?)
+    }
+}
+
+impl<T> MessageSender<T> for mpsc::SyncSender<T> {
+    fn send(&self, message: T, backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        send_without_eviction(
+            message,
+            backpressure,
+            |message| Self::send(self, message).map_err(|error| error.0),
+            |message| match self.try_send(message) {
+                Ok(()) => Ok(()),
+                Err(mpsc::TrySendError::Full(message)) => Err(SendError::Full(message)),
+                Err(mpsc::TrySendError::Disconnected(message)) => {
+                    Err(SendError::Disconnected(message))
+                }
+            },
+        )
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+impl<T> MessageSender<T> for crossbeam_channel::Sender<T> {
+    fn send(&self, message: T, backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        send_without_eviction(
+            message,
+            backpressure,
+            |message| Self::send(self, message).map_err(|error| error.0),
+            |message| match self.try_send(message) {
+                Ok(()) => Ok(()),
+                Err(crossbeam_channel::TrySendError::Full(message)) => {
+                    Err(SendError::Full(message))
+                }
+                Err(crossbeam_channel::TrySendError::Disconnected(message)) => {
+                    Err(SendError::Disconnected(message))
+                }
+            },
+        )
+    }
+}
+
+#[cfg(feature = "flume")]
+impl<T> MessageSender<T> for flume::Sender<T> {
+    fn send(&self, message: T, backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        send_without_eviction(
+            message,
+            backpressure,
+            |message| Self::send(self, message).map_err(|error| error.0),
+            |message| match self.try_send(message) {
+                Ok(()) => Ok(()),
+                Err(flume::TrySendError::Full(message)) => Err(SendError::Full(message)),
+                Err(flume::TrySendError::Disconnected(message)) => {
+                    Err(SendError::Disconnected(message))
+                }
+            },
+        )
+    }
+
+    fn is_closed(&self) -> bool {
+        self.is_disconnected()
+    }
+}
+
+/// Unbounded, never full. Sending does not wait, so it is fine from async code.
+#[cfg(feature = "tokio")]
+impl<T> MessageSender<T> for tokio::sync::mpsc::UnboundedSender<T> {
+    fn send(&self, message: T, _backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        Self::send(self, message).map_err(|tokio::sync::mpsc::error::SendError(message)| {
+            SendError::Disconnected(message)
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        Self::is_closed(self)
+    }
+}
+
+/// Sends the messages as user events of the event loop, they arrive in
+/// `ApplicationHandler::user_event`. The event loop queue is unbounded.
+#[cfg(feature = "winit")]
+impl<T: 'static> MessageSender<T> for winit::event_loop::EventLoopProxy<T> {
+    fn send(&self, message: T, _backpressure: Option<Backpressure>) -> Result<(), SendError<T>> {
+        self.send_event(message)
+            .map_err(|winit::event_loop::EventLoopClosed(message)| SendError::Disconnected(message))
+    }
+}
+
+/// Applies `backpressure` on a channel whose sender cannot evict queued messages, `DropOldest`
+/// then fails like `Error`. Without a policy a full channel blocks.
+fn send_without_eviction<T>(
+    message: T,
+    backpressure: Option<Backpressure>,
+    send: impl FnOnce(T) -> Result<(), T>,
+    try_send: impl FnOnce(T) -> Result<(), SendError<T>>,
+) -> Result<(), SendError<T>> {
+    match backpressure.unwrap_or_default() {
+        Backpressure::Block => send(message).map_err(SendError::Disconnected),
+        Backpressure::Error | Backpressure::DropOldest => try_send(message),
+        Backpressure::DropNewest => match try_send(message) {
+            Err(SendError::Full(_)) => Ok(()),
+            result => result,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_sender_drop_newest_discards_the_sent_message() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        MessageSender::send(&sender, 1, Some(Backpressure::DropNewest)).unwrap();
+        MessageSender::send(&sender, 2, Some(Backpressure::DropNewest)).unwrap();
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn sync_sender_drop_oldest_rejects_when_full() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        MessageSender::send(&sender, 1, Some(Backpressure::DropOldest)).unwrap();
+        assert!(matches!(
+            MessageSender::send(&sender, 2, Some(Backpressure::DropOldest)),
+            Err(SendError::Full(2))
+        ));
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [1]);
+    }
+}
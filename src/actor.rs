@@ -0,0 +1,217 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{
+    channel_protocol::{self, Errors, Protocol, ProtocolKind, ProtocolMessage},
+    config::ProtocolConfig,
+};
+
+impl Protocol {
+    /// Protocol of an actor, made of the `&mut self` methods of its impl block. Methods marked
+    /// `#[channel_actor(skip)]` or `#[channel_actor(reply_dropped)]` are left out and the
+    /// markers are removed from the impl block.
+    fn from_actor(item: &mut syn::ItemImpl, config: ProtocolConfig) -> syn::Result<Self> {
+        if let Some((_, path, _)) = &item.trait_ {
+            return Err(syn::Error::new_spanned(
+                path,
+                "`channel_actor` expects an inherent impl block",
+            ));
+        }
+        if !item.generics.params.is_empty() || item.generics.where_clause.is_some() {
+            return Err(syn::Error::new_spanned(
+                &item.generics,
+                "generic actors are not supported",
+            ));
+        }
+        let ident = match &*item.self_ty {
+            syn::Type::Path(syn::TypePath { qself: None, path }) => {
+                path.segments.last().map(|segment| segment.ident.clone())
+            }
+            _ => None,
+        }
+        .ok_or_else(|| {
+            syn::Error::new_spanned(&item.self_ty, "expected the name of the actor type")
+        })?;
+        let mut errors = Errors::default();
+
+        let mut messages = Vec::new();
+        let mut reply_dropped = None;
+        for impl_item in &mut item.items {
+            let syn::ImplItem::Fn(method) = impl_item else {
+                continue;
+            };
+            let marker = match errors.ok(take_marker(&mut method.attrs)) {
+                Some(marker) => marker,
+                None => continue,
+            };
+            if let Some(Marker::ReplyDropped) = marker {
+                if reply_dropped.is_some() {
+                    errors.push(syn::Error::new_spanned(
+                        &method.sig.ident,
+                        "only one method can be marked `reply_dropped`",
+                    ));
+                }
+                reply_dropped = Some(method.sig.ident.clone());
+            }
+            let is_message = marker.is_none()
+                && matches!(
+                    method.sig.receiver(),
+                    Some(syn::Receiver {
+                        reference: Some(_),
+                        mutability: Some(_),
+                        colon_token: None,
+                        ..
+                    })
+                );
+            if !is_message {
+                // They would be left on a method the macro does not turn into a message.
+                for attr in method.attrs.iter().filter(|attr| is_client_attr(attr)) {
+                    errors.push(syn::Error::new_spanned(
+                        attr,
+                        "this method is not a message of the actor, only `&mut self` methods are",
+                    ));
+                }
+                continue;
+            }
+
+            // `#[timeout]` and `#[backpressure]` only make sense to the macro.
+            let (client_attrs, other_attrs) = std::mem::take(&mut method.attrs)
+                .into_iter()
+                .partition::<Vec<_>, _>(is_client_attr);
+            method.attrs = other_attrs;
+
+            // `mut` bindings belong to the body of the method, not to the message.
+            let mut sig = syn::Signature {
+                constness: None,
+                ..method.sig.clone()
+            };
+            for input in &mut sig.inputs {
+                if let syn::FnArg::Typed(arg) = input
+                    && let syn::Pat::Ident(pat) = &mut *arg.pat
+                {
+                    pat.mutability = None;
+                }
+            }
+
+            // Only the signature makes the message, other attributes stay on the method.
+            let declaration = syn::TraitItem::Fn(syn::TraitItemFn {
+                attrs: method
+                    .attrs
+                    .iter()
+                    .filter(|attr| {
+                        ["doc", "cfg", "deprecated"]
+                            .iter()
+                            .any(|name| attr.path().is_ident(name))
+                    })
+                    .cloned()
+                    .chain(client_attrs)
+                    .collect(),
+                sig,
+                default: None,
+                semi_token: Some(Default::default()),
+            });
+            if let Some(message) = errors.ok(ProtocolMessage::try_from(declaration)) {
+                messages.push(message);
+            }
+        }
+
+        let protocol = Self {
+            docs: Vec::new(),
+            vis: config.vis.clone().unwrap_or(syn::Visibility::Inherited),
+            ident,
+            generics: syn::Generics::default(),
+            sub_protocols: Vec::new(),
+            messages,
+            config,
+            kind: ProtocolKind::Actor {
+                self_ty: item.self_ty.clone(),
+                reply_dropped,
+            },
+        };
+        protocol.validate(&mut errors);
+
+        errors.finish()?;
+        Ok(protocol)
+    }
+}
+
+/// Role given to a method by a `#[channel_actor(...)]` marker.
+enum Marker {
+    /// Not part of the protocol.
+    Skip,
+    /// Called with the method name when a reply could not be delivered, takes
+    /// `(&mut self, &'static str)`.
+    ReplyDropped,
+}
+
+/// Whether `attr` is `#[timeout]` or `#[backpressure]`, read by the macro for the client.
+fn is_client_attr(attr: &syn::Attribute) -> bool {
+    attr.path().is_ident("timeout") || attr.path().is_ident("backpressure")
+}
+
+/// Removes the `#[channel_actor(...)]` marker of a method and returns it.
+fn take_marker(attrs: &mut Vec<syn::Attribute>) -> syn::Result<Option<Marker>> {
+    let Some(index) = attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("channel_actor"))
+    else {
+        return Ok(None);
+    };
+    let attr = attrs.remove(index);
+    let mut marker = None;
+    attr.parse_nested_meta(|meta| {
+        if marker.is_some() {
+            return Err(meta.error("a method takes a single marker"));
+        }
+        if meta.path.is_ident("skip") {
+            marker = Some(Marker::Skip);
+        } else if meta.path.is_ident("reply_dropped") {
+            marker = Some(Marker::ReplyDropped);
+        } else {
+            return Err(meta.error("unknown marker, expected `skip` or `reply_dropped`"));
+        }
+        Ok(())
+    })?;
+    Ok(marker)
+}
+
+/// Removes the attributes only the macro understands from the methods, for an impl block
+/// emitted without its protocol.
+fn strip_macro_attrs(item: &mut syn::ItemImpl) {
+    for impl_item in &mut item.items {
+        if let syn::ImplItem::Fn(method) = impl_item {
+            method.attrs.retain(|attr| {
+                !["channel_actor", "timeout", "backpressure"]
+                    .iter()
+                    .any(|name| attr.path().is_ident(name))
+            });
+        }
+    }
+}
+
+pub fn build(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let mut item = match syn::parse2::<syn::ItemImpl>(input) {
+        Ok(item) => item,
+        Err(error) => return error.to_compile_error(),
+    };
+    let protocol = match ProtocolConfig::parse_actor(attr)
+        .and_then(|config| Protocol::from_actor(&mut item, config))
+    {
+        Ok(protocol) => protocol,
+        Err(error) => {
+            // The impl block is kept so that the code using it reports no spurious errors.
+            strip_macro_attrs(&mut item);
+            let error = error.to_compile_error();
+            return quote! {
+                #item
+                #error
+            };
+        }
+    };
+
+    let items = channel_protocol::render(&protocol);
+    quote! {
+        #item
+        #items
+    }
+}
@@ -1,84 +1,691 @@
+use std::collections::HashMap;
+
+use convert_case::{Case, Casing};
 use proc_macro2::TokenStream;
-use quote::{ToTokens, quote};
-use syn::{parse::Parse, punctuated::Punctuated};
+use quote::{ToTokens, format_ident, quote};
+use syn::punctuated::Punctuated;
+
+use crate::{
+    client,
+    config::{self, ProtocolConfig},
+    enum_message, handler, protocol_trait,
+};
 
-use crate::{client, enum_message, handler};
+/// Method names used by the generated client and handler traits.
+const RESERVED_METHOD_NAMES: &[&str] = &[
+    "new",
+    "from_sender",
+    "dispatch",
+    "_dispatch",
+    "dispatch_with_state",
+    "send_message",
+    "reply_dropped",
+    "with_timeout",
+    "bounded",
+    "downgrade",
+    "is_connected",
+];
 
 #[derive(Debug)]
 pub struct Protocol {
+    /// Doc comments of the trait, kept on the emitted trait.
+    pub docs: Vec<syn::Attribute>,
     pub vis: syn::Visibility,
     pub ident: syn::Ident,
+    /// Type parameters and where clause of the trait, carried by every generated item.
+    pub generics: syn::Generics,
+    /// Protocols composed through supertraits, nested in the message enum.
+    pub sub_protocols: Vec<SubProtocol>,
     pub messages: Vec<ProtocolMessage>,
+    pub config: ProtocolConfig,
+    pub kind: ProtocolKind,
+}
+
+/// What a protocol is declared with.
+#[derive(Debug, Default)]
+pub enum ProtocolKind {
+    /// A trait given to `#[channel_protocol]`, every item is generated.
+    #[default]
+    Trait,
+    /// A hand-written message enum given to `#[derive(ChannelProtocol)]`, only the client and
+    /// the handlers are generated.
+    Enum,
+    /// An inherent impl block given to `#[channel_actor]`, its `&mut self` methods are the
+    /// messages and an inherent `dispatch` replaces the handler traits.
+    Actor {
+        self_ty: Box<syn::Type>,
+        /// Method marked `#[channel_actor(reply_dropped)]`, called when a reply is not
+        /// delivered.
+        reply_dropped: Option<syn::Ident>,
+    },
+}
+
+/// A protocol composed into another one by naming it as a supertrait.
+///
+/// Only the path to its trait is known, its generated items are found by the default naming
+/// convention: `{Trait}Message`, `{Trait}Sink` and `Handle{Trait}` next to the trait.
+#[derive(Debug)]
+pub struct SubProtocol {
+    pub path: syn::Path,
 }
 
-impl Parse for Protocol {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let vis: syn::Visibility = input.parse()?;
-        let _: syn::Token![trait] = input.parse()?;
-        let ident: syn::Ident = input.parse()?;
-        let content;
-        let _ = syn::braced!(content in input);
-        let mut messages = Vec::new();
-        while !content.is_empty() {
-            messages.push(content.parse()?);
+impl TryFrom<syn::TypeParamBound> for SubProtocol {
+    type Error = syn::Error;
+
+    fn try_from(bound: syn::TypeParamBound) -> syn::Result<Self> {
+        let error = |bound: &dyn ToTokens| {
+            syn::Error::new_spanned(
+                bound,
+                "only protocol traits are supported as supertraits of a protocol trait",
+            )
+        };
+
+        let syn::TypeParamBound::Trait(bound) = bound else {
+            return Err(error(&bound));
+        };
+        if bound.paren_token.is_some()
+            || !matches!(bound.modifier, syn::TraitBoundModifier::None)
+            || bound.lifetimes.is_some()
+        {
+            return Err(error(&bound));
         }
-        Ok(Self {
-            vis,
-            ident,
+        let Some(segment) = bound.path.segments.last() else {
+            return Err(error(&bound));
+        };
+        match &segment.arguments {
+            syn::PathArguments::None => {}
+            syn::PathArguments::AngleBracketed(arguments)
+                if arguments
+                    .args
+                    .iter()
+                    .all(|arg| matches!(arg, syn::GenericArgument::Type(_))) => {}
+            arguments => {
+                return Err(syn::Error::new_spanned(
+                    arguments,
+                    "only type arguments are supported on a composed protocol",
+                ));
+            }
+        }
+
+        Ok(Self { path: bound.path })
+    }
+}
+
+impl Protocol {
+    /// Protocol declared by a trait, validated against the options of its attribute.
+    fn from_trait(item: syn::ItemTrait, config: ProtocolConfig) -> syn::Result<Self> {
+        let mut errors = Errors::default();
+
+        let mut docs = Vec::new();
+        for attr in item.attrs {
+            if attr.path().is_ident("doc") {
+                docs.push(attr);
+            } else {
+                errors.push(syn::Error::new_spanned(
+                    attr,
+                    "unsupported attribute on a protocol trait, expected `doc`",
+                ));
+            }
+        }
+        if let Some(unsafety) = &item.unsafety {
+            errors.push(syn::Error::new_spanned(
+                unsafety,
+                "a protocol trait cannot be `unsafe`",
+            ));
+        }
+        if let Some(auto_token) = &item.auto_token {
+            errors.push(syn::Error::new_spanned(
+                auto_token,
+                "a protocol trait cannot be `auto`",
+            ));
+        }
+        for param in &item.generics.params {
+            if !matches!(param, syn::GenericParam::Type(_)) {
+                errors.push(syn::Error::new_spanned(
+                    param,
+                    "only type parameters are supported on a protocol trait",
+                ));
+            }
+        }
+        let sub_protocols = item
+            .supertraits
+            .into_iter()
+            .filter_map(|bound| errors.ok(SubProtocol::try_from(bound)))
+            .collect();
+
+        let messages = item
+            .items
+            .into_iter()
+            .filter_map(|item| errors.ok(ProtocolMessage::try_from(item)))
+            .collect();
+
+        let protocol = Self {
+            docs,
+            vis: item.vis,
+            ident: item.ident,
+            generics: item.generics,
+            sub_protocols,
             messages,
-        })
+            config,
+            kind: ProtocolKind::Trait,
+        };
+        protocol.validate(&mut errors);
+
+        errors.finish()?;
+        Ok(protocol)
+    }
+
+    /// Rejects drop policies that could discard a call with a reply: its caller would see the
+    /// reply sender dropped, reported as a panicked handler.
+    fn validate_backpressure(&self, errors: &mut Errors) {
+        // `tokio` clients reject every policy.
+        if self.config.tokio {
+            return;
+        }
+        let is_drop = |policy: &syn::Ident| policy == "DropNewest" || policy == "DropOldest";
+        let policy_name = |policy: &syn::Ident| policy.to_string().to_case(Case::Snake);
+        let protocol_policy = self.config.backpressure.as_ref();
+        let evicting = self
+            .messages
+            .iter()
+            .filter_map(|message| self.effective_backpressure(Some(message)))
+            .chain(protocol_policy.filter(|_| self.config.batch))
+            .any(|policy| policy == "DropOldest");
+        let batch_policy = protocol_policy.filter(|policy| self.config.batch && is_drop(policy));
+
+        for message in self.messages.iter().filter(|message| message.has_reply()) {
+            let name = &message.ident;
+            if let Some(policy) = self
+                .effective_backpressure(Some(message))
+                .filter(|policy| is_drop(policy))
+            {
+                errors.push(syn::Error::new_spanned(
+                    message.backpressure.as_ref().unwrap_or(name),
+                    format!(
+                        "`{name}` has a reply, the `{}` policy would discard its call without reporting it, use `block` or `error`",
+                        policy_name(policy),
+                    ),
+                ));
+            } else if evicting {
+                errors.push(syn::Error::new_spanned(
+                    name,
+                    format!(
+                        "`{name}` has a reply, the `drop_oldest` policy of other calls could evict its call without reporting it"
+                    ),
+                ));
+            } else if let Some(policy) = batch_policy {
+                errors.push(syn::Error::new_spanned(
+                    name,
+                    format!(
+                        "`{name}` has a reply, batches sent with the `{}` policy of the protocol could discard its call without reporting it",
+                        policy_name(policy),
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Checks the constraints that span several messages: variant names must be unique
+    /// and method names must not shadow generated items.
+    pub fn validate(&self, errors: &mut Errors) {
+        self.validate_backpressure(errors);
+
+        for param in self.generics.type_params() {
+            let used_by_sub_protocol = self.sub_protocols.iter().any(|sub_protocol| {
+                let path = &sub_protocol.path;
+                self.mentioned_type_params(&syn::parse_quote!(#path))
+                    .contains(&&param.ident)
+            });
+            let used = used_by_sub_protocol
+                || self.messages.iter().any(|message| {
+                    let output = match &message.output {
+                        syn::ReturnType::Default => None,
+                        syn::ReturnType::Type(_, ty) => Some(&**ty),
+                    };
+                    message
+                        .args
+                        .iter()
+                        .map(|arg| &arg.ty)
+                        .chain(output)
+                        .any(|ty| self.mentioned_type_params(ty).contains(&&param.ident))
+                });
+            if !used {
+                errors.push(syn::Error::new_spanned(
+                    &param.ident,
+                    format!(
+                        "type parameter `{}` is not used by any protocol method",
+                        param.ident
+                    ),
+                ));
+            }
+        }
+
+        let mut variants = HashMap::new();
+        for sub_protocol in &self.sub_protocols {
+            let variant = sub_protocol.variant_ident();
+            if let Some(previous) = variants.insert(variant.to_string(), variant.clone()) {
+                errors.push(syn::Error::new_spanned(
+                    &sub_protocol.path,
+                    format!("protocol `{previous}` is composed more than once"),
+                ));
+            }
+        }
+        if self.config.batch {
+            variants.insert("Batch".to_owned(), format_ident!("batch"));
+        }
+        for message in &self.messages {
+            let name = message.ident.to_string();
+            if self.config.tokio
+                && let Some(timeout) = &message.timeout
+            {
+                errors.push(syn::Error::new_spanned(
+                    timeout,
+                    "timeouts are not supported by the async methods of `tokio` clients",
+                ));
+            }
+            if self.config.tokio
+                && let Some(policy) = &message.backpressure
+            {
+                errors.push(syn::Error::new_spanned(policy, config::TOKIO_BACKPRESSURE));
+            }
+
+            if RESERVED_METHOD_NAMES.contains(&name.as_str())
+                || (self.config.batch && ["batch", "try_batch"].contains(&name.as_str()))
+                || (self.config.tokio && name == "spawn")
+            {
+                errors.push(syn::Error::new_spanned(
+                    &message.ident,
+                    format!("`{name}` clashes with a method generated by `channel_protocol`"),
+                ));
+                continue;
+            }
+
+            if let Some(other) = self
+                .messages
+                .iter()
+                .find(|other| message.ident == other.try_ident())
+            {
+                errors.push(syn::Error::new_spanned(
+                    &message.ident,
+                    format!(
+                        "`{name}` clashes with the `try_*` client method generated for `{}`",
+                        other.ident
+                    ),
+                ));
+            }
+            if let Some(other) = self
+                .messages
+                .iter()
+                .find(|other| other.has_deferred() && message.ident == other.deferred_ident())
+            {
+                errors.push(syn::Error::new_spanned(
+                    &message.ident,
+                    format!(
+                        "`{name}` clashes with the `*_deferred` client method generated for `{}`",
+                        other.ident
+                    ),
+                ));
+            }
+
+            if let Some(other) = self
+                .messages
+                .iter()
+                .find(|other| self.has_async_variant(other) && message.ident == other.async_ident())
+            {
+                errors.push(syn::Error::new_spanned(
+                    &message.ident,
+                    format!(
+                        "`{name}` clashes with the `*_async` client method generated for `{}`",
+                        other.ident
+                    ),
+                ));
+            }
+
+            let variant = &message.variant;
+            if let Some(previous) = variants.insert(variant.to_string(), message.ident.clone()) {
+                errors.push(syn::Error::new_spanned(
+                    &message.ident,
+                    format!(
+                        "`{name}` maps to the same message variant `{variant}` as `{previous}`"
+                    ),
+                ));
+            }
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct ProtocolMessage {
+    /// Doc comments, copied onto the client method, the handler methods and the enum variant.
+    pub docs: Vec<syn::Attribute>,
+    /// `#[cfg]` and `#[deprecated]` attributes, applied to every item generated for the message.
+    pub attrs: Vec<syn::Attribute>,
+    /// `async` methods are awaited by callers and get an async handler trait.
+    pub asyncness: Option<syn::Token![async]>,
     pub ident: syn::Ident,
+    /// Variant of the message enum carrying the message.
+    pub variant: syn::Ident,
+    pub fields: MessageFields,
     pub args: syn::punctuated::Punctuated<ProtocolMessageFnArg, syn::Token![,]>,
     pub output: syn::ReturnType,
+    /// Default body, used as the default implementation of the handler method.
+    pub default: Option<syn::Block>,
+    /// Milliseconds the client waits for the reply, from `#[timeout(ms = 50)]`.
+    pub timeout: Option<syn::LitInt>,
+    /// `Backpressure` variant overriding the one of the channel, from
+    /// `#[backpressure(drop_newest)]`.
+    pub backpressure: Option<syn::Ident>,
+}
+
+/// Layout of the arguments and of the `tx` reply sender in a message variant.
+#[derive(Debug)]
+pub enum MessageFields {
+    /// Arguments in the generated parameter struct, followed by the reply sender.
+    Params,
+    /// Unit variant of a hand-written enum.
+    Unit,
+    /// Named fields of a hand-written variant, the reply sender being the given field.
+    Named { reply: Option<syn::Ident> },
+    /// Unnamed fields of a hand-written variant, the reply sender coming last.
+    Unnamed,
 }
 
-impl Parse for ProtocolMessage {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let _: syn::Token![fn] = input.parse()?;
-        let ident: syn::Ident = input.parse()?;
-        let content;
-        let _ = syn::parenthesized!(content in input);
-        let args = Punctuated::parse_terminated(&content)?;
-        let output: syn::ReturnType = input.parse()?;
-        let _: syn::Token![;] = input.parse()?;
+impl TryFrom<syn::TraitItem> for ProtocolMessage {
+    type Error = syn::Error;
+
+    fn try_from(item: syn::TraitItem) -> syn::Result<Self> {
+        let syn::TraitItem::Fn(item) = item else {
+            return Err(syn::Error::new_spanned(
+                item,
+                "only method declarations are supported in a protocol trait",
+            ));
+        };
+        let mut errors = Errors::default();
+
+        let mut docs = Vec::new();
+        let mut attrs = Vec::new();
+        let mut timeout = None;
+        let mut backpressure = None;
+        for attr in item.attrs {
+            if attr.path().is_ident("doc") {
+                docs.push(attr);
+            } else if attr.path().is_ident("cfg") || attr.path().is_ident("deprecated") {
+                attrs.push(attr);
+            } else if attr.path().is_ident("timeout") {
+                if timeout.is_some() {
+                    errors.push(syn::Error::new_spanned(
+                        &attr,
+                        "a protocol method has a single timeout",
+                    ));
+                }
+                timeout = errors.ok(parse_timeout(&attr));
+            } else if attr.path().is_ident("backpressure") {
+                if backpressure.is_some() {
+                    errors.push(syn::Error::new_spanned(
+                        &attr,
+                        "a protocol method has a single backpressure policy",
+                    ));
+                }
+                backpressure = errors.ok(attr
+                    .parse_args()
+                    .and_then(|policy| config::parse_backpressure(&policy)));
+            } else {
+                errors.push(syn::Error::new_spanned(
+                    attr,
+                    "unsupported attribute on a protocol method, expected `doc`, `cfg`, `deprecated`, `timeout` or `backpressure`",
+                ));
+            }
+        }
+
+        let sig = item.sig;
+        if let Some(constness) = &sig.constness {
+            errors.push(syn::Error::new_spanned(
+                constness,
+                "protocol methods cannot be `const`",
+            ));
+        }
+        if let Some(unsafety) = &sig.unsafety {
+            errors.push(syn::Error::new_spanned(
+                unsafety,
+                "protocol methods cannot be `unsafe`",
+            ));
+        }
+        if let Some(abi) = &sig.abi {
+            errors.push(syn::Error::new_spanned(
+                abi,
+                "protocol methods cannot declare an ABI",
+            ));
+        }
+        if !sig.generics.params.is_empty() {
+            errors.push(syn::Error::new_spanned(
+                &sig.generics.params,
+                "generic protocol methods are not supported",
+            ));
+        }
+        if let Some(where_clause) = &sig.generics.where_clause {
+            errors.push(syn::Error::new_spanned(
+                where_clause,
+                "where clauses are not supported on protocol methods",
+            ));
+        }
+        if let Some(variadic) = &sig.variadic {
+            errors.push(syn::Error::new_spanned(
+                variadic,
+                "variadic protocol methods are not supported",
+            ));
+        }
+
+        if let Some(timeout) = &timeout {
+            if sig.asyncness.is_some() {
+                errors.push(syn::Error::new_spanned(
+                    timeout,
+                    "timeouts are not supported on `async` protocol methods",
+                ));
+            }
+            if matches!(sig.output, syn::ReturnType::Default) {
+                errors.push(syn::Error::new_spanned(
+                    timeout,
+                    "only protocol methods returning a value can have a timeout",
+                ));
+            }
+        }
+
+        let mut args = Punctuated::new();
+        for arg in sig.inputs {
+            if let syn::FnArg::Receiver(receiver) = &arg {
+                // The client takes `&self` and the handlers `&mut self`, whatever is declared.
+                if receiver.reference.is_none() || receiver.colon_token.is_some() {
+                    errors.push(syn::Error::new_spanned(
+                        receiver,
+                        "only `&self` and `&mut self` receivers are supported in protocol methods",
+                    ));
+                }
+                continue;
+            }
+            let Some(arg) = errors.ok(ProtocolMessageFnArg::try_from(arg)) else {
+                continue;
+            };
+            if args
+                .iter()
+                .any(|other: &ProtocolMessageFnArg| other.ident == arg.ident)
+            {
+                errors.push(syn::Error::new_spanned(
+                    &arg.ident,
+                    format!("argument `{}` is declared more than once", arg.ident),
+                ));
+            }
+            args.push(arg);
+        }
+
+        errors.finish()?;
         Ok(Self {
-            ident,
+            docs,
+            attrs,
+            asyncness: sig.asyncness,
+            variant: format_ident!("{}", sig.ident.to_string().to_case(Case::Pascal)),
+            ident: sig.ident,
+            fields: MessageFields::Params,
             args,
-            output,
+            output: sig.output,
+            default: item.default,
+            timeout,
+            backpressure,
         })
     }
 }
 
-impl ToTokens for ProtocolMessage {
-    fn to_tokens(&self, tokens: &mut TokenStream) {
+/// Milliseconds of a `#[timeout(ms = 50)]` attribute.
+fn parse_timeout(attr: &syn::Attribute) -> syn::Result<syn::LitInt> {
+    let mut ms = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("ms") {
+            let value: syn::LitInt = meta.value()?.parse()?;
+            value.base10_parse::<u64>()?;
+            ms = Some(value);
+            Ok(())
+        } else {
+            Err(meta.error("unknown option, expected `ms`"))
+        }
+    })?;
+    ms.ok_or_else(|| syn::Error::new_spanned(attr, "expected `#[timeout(ms = 50)]`"))
+}
+
+impl ProtocolMessage {
+    /// Declaration of the method in the protocol trait, `async` if `asyncness` is given.
+    pub fn declaration(&self, asyncness: Option<syn::Token![async]>) -> TokenStream {
         let Self {
-            ident,
-            args,
-            output,
+            docs, attrs, ident, ..
         } = self;
-        tokens.extend(quote! {
-            fn #ident(#args) #output;
-        });
+        let args = self.client_args();
+        let output = self.client_output();
+        quote! {
+            #(#docs)*
+            #(#attrs)*
+            #asyncness fn #ident(&self, #args) #output;
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct ProtocolMessageFnArg {
+    /// Doc comments, copied onto the matching field of the parameter struct.
+    pub docs: Vec<syn::Attribute>,
     pub ident: syn::Ident,
+    /// Type stored in the parameter struct and given to the handlers.
     pub ty: syn::Type,
+    /// Type accepted by the client, differs from `ty` when the argument is converted.
+    pub client_ty: syn::Type,
+    pub conversion: Option<ArgConversion>,
+}
+
+/// Conversion applied by the client to an `impl Into<T>` or `impl AsRef<T>` argument before it
+/// is stored in the parameter struct.
+#[derive(Debug)]
+pub enum ArgConversion {
+    /// `impl Into<T>`, stored as `T`.
+    Into,
+    /// `impl AsRef<T>`, stored as the owned form of `T`.
+    AsRef,
+}
+
+impl ArgConversion {
+    /// Splits an `impl Into<T>` or `impl AsRef<T>` type into its conversion and stored type.
+    fn parse(ty: &syn::TypeImplTrait) -> syn::Result<(Self, syn::Type)> {
+        let error = || {
+            syn::Error::new_spanned(
+                ty,
+                "only `impl Into<T>` and `impl AsRef<T>` are supported as `impl Trait` arguments",
+            )
+        };
+
+        let mut bounds = ty.bounds.iter();
+        let (Some(syn::TypeParamBound::Trait(bound)), None) = (bounds.next(), bounds.next()) else {
+            return Err(error());
+        };
+        let Some(segment) = bound.path.segments.last() else {
+            return Err(error());
+        };
+        let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+            return Err(error());
+        };
+        let mut arguments = arguments.args.iter();
+        let (Some(syn::GenericArgument::Type(target)), None) = (arguments.next(), arguments.next())
+        else {
+            return Err(error());
+        };
+
+        if segment.ident == "Into" {
+            Ok((Self::Into, target.clone()))
+        } else if segment.ident == "AsRef" {
+            Ok((
+                Self::AsRef,
+                syn::parse_quote! { <#target as std::borrow::ToOwned>::Owned },
+            ))
+        } else {
+            Err(error())
+        }
+    }
 }
 
-impl Parse for ProtocolMessageFnArg {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let ident: syn::Ident = input.parse()?;
-        let _: syn::Token![:] = input.parse()?;
-        let ty: syn::Type = input.parse()?;
-        Ok(Self { ident, ty })
+impl TryFrom<syn::FnArg> for ProtocolMessageFnArg {
+    type Error = syn::Error;
+
+    fn try_from(arg: syn::FnArg) -> syn::Result<Self> {
+        let arg = match arg {
+            syn::FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "a receiver must be the first argument",
+                ));
+            }
+            syn::FnArg::Typed(arg) => arg,
+        };
+
+        let mut docs = Vec::new();
+        for attr in arg.attrs {
+            if attr.path().is_ident("doc") {
+                docs.push(attr);
+            } else if attr.path().is_ident("cfg") {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "`cfg` is not supported on protocol method arguments, put it on the method instead",
+                ));
+            } else {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "unsupported attribute on a protocol method argument, expected `doc`",
+                ));
+            }
+        }
+
+        match *arg.pat {
+            syn::Pat::Ident(syn::PatIdent {
+                attrs: _,
+                by_ref: None,
+                mutability: None,
+                ident,
+                subpat: None,
+            }) => {
+                let (conversion, ty) = match &*arg.ty {
+                    syn::Type::ImplTrait(impl_trait) => {
+                        let (conversion, ty) = ArgConversion::parse(impl_trait)?;
+                        (Some(conversion), ty)
+                    }
+                    ty => (None, ty.clone()),
+                };
+                Ok(Self {
+                    docs,
+                    ident,
+                    ty,
+                    client_ty: *arg.ty,
+                    conversion,
+                })
+            }
+            pat => Err(syn::Error::new_spanned(
+                pat,
+                "patterns are not supported in protocol method arguments, expected a plain identifier",
+            )),
+        }
     }
 }
 
@@ -91,17 +698,71 @@ impl ToTokens for ProtocolMessageFnArg {
     }
 }
 
-pub fn build(input: TokenStream) -> TokenStream {
-    let protocol = syn::parse2::<Protocol>(input)
-        .expect("Wrong syntax. An fully valid trait declaration is expected");
+/// Accumulates errors so that every mistake in a protocol is reported at once.
+#[derive(Default)]
+pub struct Errors(Option<syn::Error>);
+
+impl Errors {
+    pub fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    pub fn ok<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+
+    pub fn finish(self) -> syn::Result<()> {
+        self.0.map_or(Ok(()), Err)
+    }
+}
+
+pub fn build(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let config = match ProtocolConfig::parse(attr) {
+        Ok(config) => config,
+        Err(error) => return error.to_compile_error(),
+    };
+    let protocol = match syn::parse2(input).and_then(|item| Protocol::from_trait(item, config)) {
+        Ok(protocol) => protocol,
+        Err(error) => return error.to_compile_error(),
+    };
+
+    render(&protocol)
+}
 
-    let message_enum = enum_message::build(&protocol);
-    let client = client::build(&protocol);
-    let handler = handler::build(&protocol);
+/// Generated items of a protocol, inside its module if it has one.
+pub fn render(protocol: &Protocol) -> TokenStream {
+    let message_enum = enum_message::build(protocol);
+    let client = client::build(protocol);
+    let handler = handler::build(protocol);
 
-    quote! {
+    let items = quote! {
         #message_enum
         #client
         #handler
+    };
+
+    let protocol_trait = protocol_trait::build(protocol);
+
+    match protocol.module_ident() {
+        Some(module) => {
+            let vis = &protocol.vis;
+            quote! {
+                #protocol_trait
+
+                #vis mod #module {
+                    #[allow(unused_imports)]
+                    use super::*;
+
+                    #items
+                }
+            }
+        }
+        None => quote! {
+            #protocol_trait
+            #items
+        },
     }
 }
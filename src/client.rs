@@ -1,102 +1,710 @@
-use convert_case::{Case, Casing};
-use itertools::Itertools;
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
-use syn::Ident;
+use quote::{ToTokens, format_ident, quote};
+use syn::ReturnType;
 
-use crate::{
-    channel_protocol::{Protocol, ProtocolMessage},
-    render::message::MessageSignatureKind,
-};
+use crate::channel_protocol::{Protocol, ProtocolKind, ProtocolMessage};
+
+/// Where a client method is generated.
+#[derive(Clone, Copy)]
+enum FnTarget {
+    /// Inherent method of the client, sending through its channel.
+    Client,
+    /// Method of the protocol trait implemented for every sink.
+    Sink,
+    /// Method of the client view returned by `with_timeout`, waiting at most `self.timeout`.
+    WithTimeout,
+    /// `*_async` variant of a sync client method, awaiting the reply instead of blocking.
+    Async,
+}
+
+/// Call sending `message` through `sender`, the shared sender of a client.
+fn send(sender: TokenStream, backpressure: &TokenStream) -> TokenStream {
+    quote! {
+        ::channel_protocol_runtime::MessageSender::send(&*#sender, message, #backpressure)
+    }
+}
+
+/// Expression waiting for the reply on `rx`, as a `Result<T, ClientError>`. An `async` method
+/// awaits it.
+fn receive_reply(
+    asyncness: Option<syn::Token![async]>,
+    timeout: Option<&TokenStream>,
+) -> TokenStream {
+    match (asyncness, timeout) {
+        (Some(_), _) => quote! {
+            rx.await.map_err(|_| ::channel_protocol_runtime::ClientError::ReplyDropped)
+        },
+        (None, None) => quote! {
+            rx.recv().map_err(|_| ::channel_protocol_runtime::ClientError::ReplyDropped)
+        },
+        (None, Some(timeout)) => quote! {
+            rx.recv_timeout(#timeout).map_err(|error| match error {
+                oneshot::RecvTimeoutError::Timeout => {
+                    ::channel_protocol_runtime::ClientError::Timeout
+                }
+                oneshot::RecvTimeoutError::Disconnected => {
+                    ::channel_protocol_runtime::ClientError::ReplyDropped
+                }
+            })
+        },
+    }
+}
 
 fn message_to_fn(
-    enum_message_name: &Ident,
+    protocol: &Protocol,
     message @ ProtocolMessage {
+        docs, attrs, ident, ..
+    }: &ProtocolMessage,
+    target: FnTarget,
+) -> TokenStream {
+    let backpressure = protocol.backpressure_arg(Some(message));
+    let (vis, attrs, send) = match target {
+        FnTarget::Client => (
+            Some(protocol.item_vis()),
+            quote! { #(#docs)* #(#attrs)* },
+            send(quote! { self.0 }, &backpressure),
+        ),
+        FnTarget::Sink => {
+            let cfg_attrs = message.cfg_attrs();
+            (
+                None,
+                quote! { #(#cfg_attrs)* },
+                quote! { self.send_message(message, #backpressure) },
+            )
+        }
+        FnTarget::WithTimeout => (
+            Some(protocol.item_vis()),
+            quote! { #(#docs)* #(#attrs)* },
+            send(quote! { self.client.0 }, &backpressure),
+        ),
+        FnTarget::Async => {
+            let timeout_note = if message.timeout.is_some() {
+                " The timeout of the method does not apply."
+            } else {
+                ""
+            };
+            let doc = format!(
+                "Like [`Self::{ident}`], but awaits the reply instead of blocking the thread, for callers running in an async task.{timeout_note}"
+            );
+            (
+                Some(protocol.item_vis()),
+                quote! { #[doc = #doc] #(#attrs)* },
+                send(quote! { self.0 }, &backpressure),
+            )
+        }
+    };
+    let (asyncness, ident) = match target {
+        FnTarget::Async => (Some(Default::default()), message.async_ident()),
+        _ => (protocol.client_asyncness(message), ident.clone()),
+    };
+    let (timeout, output) = match target {
+        FnTarget::WithTimeout => (Some(quote! { self.timeout }), message.timed_output()),
+        FnTarget::Async => (None, message.untimed_output()),
+        FnTarget::Client | FnTarget::Sink => (message.timeout_duration(), message.client_output()),
+    };
+    let args = message.client_args();
+    let reply = receive_reply(asyncness, timeout.as_ref());
+    let send_and_reply = if message.result_types().is_some() {
+        quote! {
+            #send
+                .map_err(::channel_protocol_runtime::CallError::from)?;
+            #reply
+                .map_err(::channel_protocol_runtime::CallError::from)?
+                .map_err(::channel_protocol_runtime::CallError::Failed)
+        }
+    } else if timeout.is_some() {
+        quote! {
+            #send
+                .map_err(::channel_protocol_runtime::ClientError::from)?;
+            #reply
+        }
+    } else {
+        quote! {
+            #send.unwrap();
+            #reply.unwrap()
+        }
+    };
+    let message_value = protocol.message_value(message);
+    let body = if message.has_reply() {
+        quote! {
+            let (tx, rx) = oneshot::channel();
+            let message = #message_value;
+            #send_and_reply
+        }
+    } else {
+        quote! {
+            let message = #message_value;
+            #send.unwrap();
+        }
+    };
+
+    quote! {
+        #attrs
+        #vis #asyncness fn #ident(&self, #args) #output {
+            #body
+        }
+    }
+}
+
+/// `try_*` variant of a client method, returning an error instead of panicking when the
+/// handler is gone.
+fn message_to_try_fn(
+    protocol: &Protocol,
+    message @ ProtocolMessage {
+        attrs,
         ident,
         output,
-        args,
         ..
     }: &ProtocolMessage,
 ) -> TokenStream {
-    let message_enum_ident = format_ident!("{}", ident.to_string().to_case(Case::Pascal));
-    match message.signature_kind() {
-        MessageSignatureKind::None => {
-            quote! {
-                pub fn #ident(&self) {
-                    let message = #enum_message_name::#message_enum_ident;
-                    self.0.send(message).unwrap();
-                }
+    let vis = protocol.item_vis();
+    let asyncness = protocol.client_asyncness(message);
+    let try_ident = message.try_ident();
+    let doc = format!(
+        "Like [`Self::{ident}`], but returns a [`ClientError`](::channel_protocol_runtime::ClientError) instead of panicking when the handler is gone."
+    );
+    let args = message.client_args();
+    let output = match output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    let message_value = protocol.message_value(message);
+    let send = send(quote! { self.0 }, &protocol.backpressure_arg(Some(message)));
+    let body = if message.has_reply() {
+        let reply = receive_reply(asyncness, message.timeout_duration().as_ref());
+        quote! {
+            let (tx, rx) = oneshot::channel();
+            let message = #message_value;
+            #send.map_err(::channel_protocol_runtime::ClientError::from)?;
+            #reply
+        }
+    } else {
+        quote! {
+            let message = #message_value;
+            #send.map_err(::channel_protocol_runtime::ClientError::from)
+        }
+    };
+
+    quote! {
+        #[doc = #doc]
+        #(#attrs)*
+        #vis #asyncness fn #try_ident(
+            &self,
+            #args
+        ) -> std::result::Result<#output, ::channel_protocol_runtime::ClientError> {
+            #body
+        }
+    }
+}
+
+/// `*_deferred` variant of a client method, sending the call and returning a handle on its
+/// reply instead of waiting for it.
+fn message_to_deferred_fn(
+    protocol: &Protocol,
+    message @ ProtocolMessage { attrs, ident, .. }: &ProtocolMessage,
+) -> TokenStream {
+    let ReturnType::Type(_, output) = &message.output else {
+        return TokenStream::new();
+    };
+    if !message.has_deferred() {
+        return TokenStream::new();
+    }
+
+    let vis = protocol.item_vis();
+    let deferred_ident = message.deferred_ident();
+    let doc = format!(
+        "Like [`Self::{ident}`], but returns a [`Pending`](::channel_protocol_runtime::Pending) handle on the reply instead of waiting for it."
+    );
+    let args = message.client_args();
+    let message_value = protocol.message_value(message);
+    let send = send(quote! { self.0 }, &protocol.backpressure_arg(Some(message)));
+
+    quote! {
+        #[doc = #doc]
+        #(#attrs)*
+        #vis fn #deferred_ident(&self, #args) -> ::channel_protocol_runtime::Pending<#output> {
+            let (tx, rx) = oneshot::channel();
+            let message = #message_value;
+            match #send {
+                Ok(()) => ::channel_protocol_runtime::Pending::new(rx),
+                Err(error) => ::channel_protocol_runtime::Pending::failed(error.into()),
             }
         }
-        MessageSignatureKind::OnlyReturn => {
-            quote! {
-                pub fn #ident(&self) #output {
-                    let (tx, rx) = oneshot::channel();
-                    let message = #enum_message_name::#message_enum_ident(tx);
-                    self.0.send(message).unwrap();
-                    rx.recv().unwrap()
+    }
+}
+
+fn functions(protocol: &Protocol, target: FnTarget) -> TokenStream {
+    protocol
+        .messages
+        .iter()
+        .map(|m| match target {
+            FnTarget::Client => {
+                let function = message_to_fn(protocol, m, target);
+                let try_function = message_to_try_fn(protocol, m);
+                let deferred_function = message_to_deferred_fn(protocol, m);
+                let async_function = protocol
+                    .has_async_variant(m)
+                    .then(|| message_to_fn(protocol, m, FnTarget::Async));
+                quote! {
+                    #function
+                    #try_function
+                    #deferred_function
+                    #async_function
                 }
             }
-        }
-        MessageSignatureKind::OnlyParam => {
-            let fields = args.iter().map(|arg| &arg.ident).collect_vec();
-            let message_struct_name = message.struct_ident();
-            quote! {
-                pub fn #ident(&self, #args) {
-                    let message = #enum_message_name::#message_enum_ident(#message_struct_name {
-                        #(#fields,)*
-                    });
-                    self.0.send(message).unwrap();
+            FnTarget::Sink | FnTarget::Async => message_to_fn(protocol, m, target),
+            FnTarget::WithTimeout => {
+                if protocol.client_asyncness(m).is_none() && m.has_reply() {
+                    message_to_fn(protocol, m, target)
+                } else {
+                    TokenStream::new()
                 }
             }
+        })
+        .collect()
+}
+
+/// The `with_timeout` method of the client and the view it returns, offering the sync
+/// request/reply methods with a timeout.
+fn with_timeout(protocol: &Protocol) -> (TokenStream, TokenStream) {
+    if !protocol
+        .messages
+        .iter()
+        .any(|m| protocol.client_asyncness(m).is_none() && m.has_reply())
+    {
+        return (TokenStream::new(), TokenStream::new());
+    }
+
+    let vis = protocol.item_vis();
+    let client_struct_name = protocol.client_ident();
+    let view_ident = format_ident!("{}WithTimeout", client_struct_name);
+    let functions = functions(protocol, FnTarget::WithTimeout);
+    let generics = &protocol.generics;
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut view_generics = generics.clone();
+    view_generics
+        .params
+        .insert(0, syn::parse_quote! { 'client });
+    let (view_impl_generics, view_ty_generics, _) = view_generics.split_for_impl();
+
+    let doc = format!(
+        "Client calls waiting at most a given time for the reply, see [`{client_struct_name}::with_timeout`]."
+    );
+    let view = quote! {
+        #[doc = #doc]
+        #vis struct #view_ident #view_generics #where_clause {
+            client: &'client #client_struct_name #ty_generics,
+            timeout: std::time::Duration,
         }
-        MessageSignatureKind::ParamReturn => {
-            let fields = args.iter().map(|arg| &arg.ident).collect_vec();
-            let message_struct_name = message.struct_ident();
-            quote! {
-                pub fn #ident(&self, #args) #output {
-                    let (tx, rx) = oneshot::channel();
-                    let message = #enum_message_name::#message_enum_ident(#message_struct_name {
-                        #(#fields,)*
-                    }, tx);
-                    self.0.send(message).unwrap();
-                    rx.recv().unwrap()
-                }
+
+        #[allow(deprecated)]
+        impl #view_impl_generics #view_ident #view_ty_generics #where_clause {
+            #functions
+        }
+    };
+    let type_args = generics.params.iter().map(|param| match param {
+        syn::GenericParam::Type(param) => param.ident.to_token_stream(),
+        syn::GenericParam::Lifetime(param) => param.lifetime.to_token_stream(),
+        syn::GenericParam::Const(param) => param.ident.to_token_stream(),
+    });
+    let method = quote! {
+        /// Calls the request/reply methods with a timeout, they return a `Timeout` error when
+        /// the handler does not reply in time.
+        #vis fn with_timeout(&self, timeout: std::time::Duration) -> #view_ident<'_ #(, #type_args)*> {
+            #view_ident {
+                client: self,
+                timeout,
             }
         }
-    }
+    };
+    (method, view)
 }
 
-fn functions(enum_message_name: &Ident, messages: &[ProtocolMessage]) -> TokenStream {
-    messages
+/// The sink trait and the protocol trait implementation for every sink, the client included.
+fn sink(protocol: &Protocol) -> TokenStream {
+    if !matches!(protocol.kind, ProtocolKind::Trait) {
+        return TokenStream::new();
+    }
+
+    let vis = protocol.item_vis();
+    let client_struct_name = protocol.client_ident();
+    let sink_ident = protocol.sink_ident();
+    let sink_type = protocol.sink_type_ident();
+    let message_enum_type = protocol.message_enum_type();
+    let functions = functions(protocol, FnTarget::Sink);
+    let protocol_ident = &protocol.ident;
+    let generics = &protocol.generics;
+    let (client_impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let supertraits = protocol
+        .sub_protocols
         .iter()
-        .map(|m| message_to_fn(enum_message_name, m))
-        .collect()
+        .map(|sub_protocol| &sub_protocol.path);
+
+    let mut impl_generics = generics.clone();
+    impl_generics.params.push(syn::parse_quote! {
+        #sink_type: #sink_ident #ty_generics #(+ #supertraits)*
+    });
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+
+    quote! {
+        #vis trait #sink_ident #generics #where_clause {
+            /// Sends `message`, applying `backpressure` if given when the channel is full.
+            fn send_message(
+                &self,
+                message: #message_enum_type,
+                backpressure: Option<::channel_protocol_runtime::Backpressure>,
+            ) -> std::result::Result<(), ::channel_protocol_runtime::SendError<#message_enum_type>>;
+        }
+
+        impl #client_impl_generics #sink_ident #ty_generics for #client_struct_name #ty_generics #where_clause {
+            fn send_message(
+                &self,
+                message: #message_enum_type,
+                backpressure: Option<::channel_protocol_runtime::Backpressure>,
+            ) -> std::result::Result<(), ::channel_protocol_runtime::SendError<#message_enum_type>> {
+                ::channel_protocol_runtime::MessageSender::send(&*self.0, message, backpressure)
+            }
+        }
+
+        #[allow(deprecated)]
+        impl #impl_generics #protocol_ident #ty_generics for #sink_type #where_clause {
+            #functions
+        }
+    }
 }
 
-pub fn build(
-    protocol @ Protocol {
-        vis,
-        ident,
-        messages,
-        ..
-    }: &Protocol,
+/// Method of the batch builder, queuing the message and returning a handle on its reply.
+fn message_to_batch_fn(
+    protocol: &Protocol,
+    message @ ProtocolMessage {
+        docs, attrs, ident, ..
+    }: &ProtocolMessage,
 ) -> TokenStream {
-    let client_struct_name = format_ident!("{}Client", ident);
+    let vis = protocol.item_vis();
+    let args = message.client_args();
+    let message_value = protocol.message_value(message);
+
+    match &message.output {
+        ReturnType::Type(_, output) => quote! {
+            #(#docs)*
+            #(#attrs)*
+            #vis fn #ident(&mut self, #args) -> ::channel_protocol_runtime::Pending<#output> {
+                let (tx, rx) = oneshot::channel();
+                self.0.push(#message_value);
+                ::channel_protocol_runtime::Pending::new(rx)
+            }
+        },
+        ReturnType::Default => quote! {
+            #(#docs)*
+            #(#attrs)*
+            #vis fn #ident(&mut self, #args) {
+                self.0.push(#message_value);
+            }
+        },
+    }
+}
+
+/// The `batch` methods of the client and the builder they hand to their closure.
+fn batch(protocol: &Protocol) -> (TokenStream, TokenStream) {
+    if !protocol.config.batch {
+        return (TokenStream::new(), TokenStream::new());
+    }
+
+    let vis = protocol.item_vis();
+    let client_struct_name = protocol.client_ident();
+    let builder_ident = format_ident!("{}Batch", client_struct_name);
+    let message_enum_ident = protocol.message_enum_ident();
+    let message_enum_type = protocol.message_enum_type();
+    let functions = protocol
+        .messages
+        .iter()
+        .map(|message| message_to_batch_fn(protocol, message));
+    let generics = &protocol.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let send = send(quote! { self.0 }, &protocol.backpressure_arg(None));
+
+    let doc = format!(
+        "Calls queued by [`{client_struct_name}::batch`], methods with a reply return a [`Pending`](::channel_protocol_runtime::Pending) handle on it."
+    );
+    let builder = quote! {
+        #[doc = #doc]
+        #vis struct #builder_ident #generics (Vec<#message_enum_type>) #where_clause;
+
+        #[allow(deprecated)]
+        impl #impl_generics #builder_ident #ty_generics #where_clause {
+            #(#functions)*
+        }
+    };
+    let methods = quote! {
+        /// Sends the calls made on the builder by `build` as a single message, so that the
+        /// handler runs them back to back without calls from other clients in between. Returns
+        /// what `build` returns, typically the [`Pending`](::channel_protocol_runtime::Pending)
+        /// replies.
+        #vis fn batch<R>(&self, build: impl FnOnce(&mut #builder_ident #ty_generics) -> R) -> R {
+            self.try_batch(build).unwrap()
+        }
+
+        /// Like [`Self::batch`], but returns a [`ClientError`](::channel_protocol_runtime::ClientError) instead of panicking when the handler is gone.
+        #vis fn try_batch<R>(
+            &self,
+            build: impl FnOnce(&mut #builder_ident #ty_generics) -> R,
+        ) -> std::result::Result<R, ::channel_protocol_runtime::ClientError> {
+            let mut batch = #builder_ident(Vec::new());
+            let ret = build(&mut batch);
+            let message = #message_enum_ident::Batch(batch.0);
+            #send.map_err(::channel_protocol_runtime::ClientError::from)?;
+            Ok(ret)
+        }
+    };
+    (methods, builder)
+}
+
+/// Sink implementations of the client for the composed protocols, nesting their messages.
+fn sub_protocol_sinks(protocol: &Protocol) -> TokenStream {
+    let client_struct_name = protocol.client_ident();
     let message_enum_ident = protocol.message_enum_ident();
-    let functions = functions(&message_enum_ident, messages);
+    let (impl_generics, ty_generics, where_clause) = protocol.generics.split_for_impl();
+
+    protocol
+        .sub_protocols
+        .iter()
+        .map(|sub_protocol| {
+            let variant_ident = sub_protocol.variant_ident();
+            let sink_path = sub_protocol.sink_path();
+            let message_enum_path = sub_protocol.message_enum_path();
+            quote! {
+                impl #impl_generics #sink_path for #client_struct_name #ty_generics #where_clause {
+                    fn send_message(
+                        &self,
+                        message: #message_enum_path,
+                        backpressure: Option<::channel_protocol_runtime::Backpressure>,
+                    ) -> std::result::Result<(), ::channel_protocol_runtime::SendError<#message_enum_path>> {
+                        let message = #message_enum_ident::#variant_ident(message);
+                        ::channel_protocol_runtime::MessageSender::send(&*self.0, message, backpressure)
+                            .map_err(|error| {
+                                error.map(|message| match message {
+                                    #message_enum_ident::#variant_ident(message) => message,
+                                    #[allow(unreachable_patterns)]
+                                    _ => unreachable!(),
+                                })
+                            })
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Constructors of the client, creating its channel when the sender is the default one.
+fn constructors(protocol: &Protocol) -> TokenStream {
+    let vis = protocol.item_vis();
+    let message_enum_type = protocol.message_enum_type();
+    let sender_type = protocol.sender_type();
+    if protocol.config.sender.is_some() {
+        return quote! {
+            #vis fn from_sender(sender: #sender_type) -> Self {
+                Self(std::sync::Arc::new(sender))
+            }
+        };
+    }
+    if protocol.config.tokio {
+        let spawn = spawn(protocol);
+        return quote! {
+            #vis fn new() -> (
+                Self,
+                ::channel_protocol_runtime::tokio::sync::mpsc::UnboundedReceiver<#message_enum_type>,
+            ) {
+                let (sender, receiver) = ::channel_protocol_runtime::tokio::sync::mpsc::unbounded_channel();
+                (Self(std::sync::Arc::new(sender)), receiver)
+            }
+
+            #spawn
+
+            #vis fn from_sender(sender: #sender_type) -> Self {
+                Self(std::sync::Arc::new(sender))
+            }
+        };
+    }
 
+    let backpressure = match &protocol.config.backpressure {
+        Some(policy) => quote! { ::channel_protocol_runtime::Backpressure::#policy },
+        None => quote! { ::channel_protocol_runtime::Backpressure::Block },
+    };
     quote! {
-        #[derive(Clone)]
-        #vis struct #client_struct_name(std::sync::mpsc::Sender<#message_enum_ident>);
+        #vis fn new() -> (Self, std::sync::mpsc::Receiver<#message_enum_type>) {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            (Self(std::sync::Arc::new(sender.into())), receiver)
+        }
+
+        /// Client over a channel holding at most `capacity` messages, see
+        /// [`Backpressure`](::channel_protocol_runtime::Backpressure) for what happens when it
+        /// is full.
+        #vis fn bounded(
+            capacity: usize,
+        ) -> (
+            Self,
+            ::channel_protocol_runtime::bounded::Receiver<#message_enum_type>,
+        ) {
+            let (sender, receiver) =
+                ::channel_protocol_runtime::bounded::channel(capacity, #backpressure);
+            (Self(std::sync::Arc::new(sender.into())), receiver)
+        }
+
+        #vis fn from_sender(sender: impl Into<#sender_type>) -> Self {
+            Self(std::sync::Arc::new(sender.into()))
+        }
+    }
+}
+
+/// `spawn` constructor of a `tokio` client, running the receive loop of its handler in a tokio
+/// task: the async handler of a protocol, the impl block of an actor.
+fn spawn(protocol: &Protocol) -> TokenStream {
+    let vis = protocol.item_vis();
+    let message_enum_type = protocol.message_enum_type();
+    let (generic, handler_type, dispatch) = match &protocol.kind {
+        ProtocolKind::Trait if !protocol.config.skip.async_handler => {
+            let async_handler = protocol.async_handler_ident();
+            let (_, ty_generics, _) = protocol.generics.split_for_impl();
+            (
+                Some(quote! { <H: #async_handler #ty_generics + 'static> }),
+                quote! { H },
+                quote! { #async_handler::dispatch(&mut handler, message).await },
+            )
+        }
+        ProtocolKind::Actor { self_ty, .. } => (
+            None,
+            self_ty.to_token_stream(),
+            quote! { handler.dispatch(message).await },
+        ),
+        _ => return TokenStream::new(),
+    };
+    let send_bounds = protocol.generics.type_params().map(|param| {
+        let ident = &param.ident;
+        quote! { #ident: Send }
+    });
+
+    quote! {
+        /// Creates a client and spawns a tokio task handing its messages to `handler`. The task
+        /// ends once every client is dropped and returns the handler.
+        #vis fn spawn #generic (
+            handler: #handler_type,
+        ) -> (Self, ::channel_protocol_runtime::tokio::task::JoinHandle<#handler_type>)
+        where
+            #(#send_bounds,)*
+            #message_enum_type: Send + 'static,
+        {
+            let (client, mut receiver) = Self::new();
+            let task = ::channel_protocol_runtime::tokio::spawn(async move {
+                let mut handler = handler;
+                while let Some(message) = receiver.recv().await {
+                    #dispatch;
+                }
+                handler
+            });
+            (client, task)
+        }
+    }
+}
+
+/// The `downgrade` method of the client and the weak client it returns.
+fn weak(protocol: &Protocol) -> (TokenStream, TokenStream) {
+    let vis = protocol.item_vis();
+    let client_struct_name = protocol.client_ident();
+    let weak_ident = format_ident!("Weak{}", client_struct_name);
+    let sender_type = protocol.sender_type();
+    let generics = &protocol.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let doc = format!(
+        "Client that does not keep the channel open, see [`{client_struct_name}::downgrade`]."
+    );
+    let is_connected_doc = format!(
+        "Whether a client still keeps the channel open and the handler did not drop its receiver, see [`{client_struct_name}::is_connected`]."
+    );
+    let weak = quote! {
+        #[doc = #doc]
+        #vis struct #weak_ident #generics (std::sync::Weak<#sender_type>) #where_clause;
+
+        impl #impl_generics Clone for #weak_ident #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        impl #impl_generics #weak_ident #ty_generics #where_clause {
+            /// Client sending through the channel, unless every client has been dropped.
+            #vis fn upgrade(&self) -> Option<#client_struct_name #ty_generics> {
+                self.0.upgrade().map(#client_struct_name)
+            }
 
-        impl #client_struct_name {
-            fn new() -> (Self, std::sync::mpsc::Receiver<#message_enum_ident>) {
-                let (sender, receiver) = std::sync::mpsc::channel();
-                (Self(sender), receiver)
+            #[doc = #is_connected_doc]
+            #vis fn is_connected(&self) -> bool {
+                self.0
+                    .upgrade()
+                    .is_some_and(|sender| !::channel_protocol_runtime::MessageSender::is_closed(&*sender))
             }
+        }
+    };
+    let method = quote! {
+        /// Whether the handler did not drop its receiver. Senders that cannot tell, such as the
+        /// std ones, always report `true` and their calls fail once it is dropped.
+        #vis fn is_connected(&self) -> bool {
+            !::channel_protocol_runtime::MessageSender::is_closed(&*self.0)
+        }
+
+        /// Client that does not keep the channel open, so that the receive loop of the handler
+        /// ends once every other client is dropped. It is turned back into a client with
+        /// `upgrade` until then.
+        #vis fn downgrade(&self) -> #weak_ident #ty_generics {
+            #weak_ident(std::sync::Arc::downgrade(&self.0))
+        }
+    };
+    (method, weak)
+}
+
+pub fn build(protocol @ Protocol { config, .. }: &Protocol) -> TokenStream {
+    if config.skip.client {
+        return TokenStream::new();
+    }
+
+    let vis = protocol.item_vis();
+    let client_struct_name = protocol.client_ident();
+    let functions = functions(protocol, FnTarget::Client);
+    let sink = sink(protocol);
+    let sub_protocol_sinks = sub_protocol_sinks(protocol);
+    let (with_timeout, with_timeout_view) = with_timeout(protocol);
+    let (batch, batch_builder) = batch(protocol);
+    let (downgrade, weak) = weak(protocol);
+    let sender_type = protocol.sender_type();
+    let constructors = constructors(protocol);
+    let generics = &protocol.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        #vis struct #client_struct_name #generics (std::sync::Arc<#sender_type>) #where_clause;
+
+        impl #impl_generics Clone for #client_struct_name #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
+        #[allow(deprecated)]
+        impl #impl_generics #client_struct_name #ty_generics #where_clause {
+            #constructors
+
+            #downgrade
+            #with_timeout
+            #batch
 
             #functions
         }
+
+        #weak
+        #with_timeout_view
+        #batch_builder
+        #sink
+        #sub_protocol_sinks
     }
 }
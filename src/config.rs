@@ -0,0 +1,262 @@
+use proc_macro2::TokenStream;
+use syn::{meta::ParseNestedMeta, parse::Parser};
+
+/// Options given to the attribute, e.g.
+/// `#[channel_protocol(client = Counter, derive(Clone), skip(debug))]`.
+#[derive(Debug, Default)]
+pub struct ProtocolConfig {
+    /// Name of the client struct, `{Trait}Client` by default.
+    pub client: Option<syn::Ident>,
+    /// Name of the message enum, `{Trait}Message` by default.
+    pub message: Option<syn::Ident>,
+    /// Name of the stateless handler trait, `Handle{Trait}` by default.
+    pub handler: Option<syn::Ident>,
+    /// Name of the stateful handler trait, `Handle{Trait}WithState` by default.
+    pub handler_with_state: Option<syn::Ident>,
+    /// Name of the async handler trait, `Handle{Trait}Async` by default.
+    pub async_handler: Option<syn::Ident>,
+    /// Name pattern of the parameter structs, `{}` is replaced by the variant name.
+    /// `{}ParamMessage` by default.
+    pub params: Option<syn::LitStr>,
+    /// Extra derives added to the message enum and the parameter structs.
+    pub derives: Vec<syn::Path>,
+    pub skip: Skip,
+    /// Generate every item inside a module, named after the trait in snake case unless a
+    /// name is given. Items then get short default names: `Client`, `Message`, `Handle`,
+    /// `HandleWithState`, `HandleAsync` and `params::{Variant}`.
+    pub module: Option<Option<syn::Ident>>,
+    /// Add a `Batch` variant to the message enum and a `batch` method to the client, sending
+    /// several calls as one message. The state of the stateful handler must then be `Clone`.
+    pub batch: bool,
+    /// `Backpressure` variant applied by the `bounded` channels when they are full, `Block` by
+    /// default.
+    pub backpressure: Option<syn::Ident>,
+    /// Sender type held by the client, given the message enum as its type parameter.
+    /// `ClientSender` by default, which also gives the `new` and `bounded` constructors.
+    pub sender: Option<syn::Path>,
+    /// Client over `tokio::sync::mpsc` whose methods are all `async`, with a `spawn`
+    /// constructor running the handler in a tokio task.
+    pub tokio: bool,
+    /// Visibility of the items generated by `#[channel_actor]`, inherited by default since an
+    /// impl block has none.
+    pub vis: Option<syn::Visibility>,
+}
+
+/// Generated artifacts that can be left out with `skip(...)`.
+#[derive(Debug, Default)]
+pub struct Skip {
+    pub client: bool,
+    pub handler: bool,
+    pub handler_with_state: bool,
+    pub async_handler: bool,
+    pub debug: bool,
+}
+
+impl ProtocolConfig {
+    pub fn parse(attr: TokenStream) -> syn::Result<Self> {
+        let mut config = Self::default();
+        syn::meta::parser(|meta| config.parse_meta(&meta)).parse2(attr)?;
+        config.check_derives()?;
+        Ok(config)
+    }
+
+    /// Options of `#[channel_actor(...)]`: those of `#[channel_protocol]` but the handler ones,
+    /// plus `vis`.
+    pub fn parse_actor(attr: TokenStream) -> syn::Result<Self> {
+        let mut config = Self::default();
+        syn::meta::parser(|meta| {
+            if meta.path.is_ident("vis") {
+                set_once(&meta, &mut config.vis)
+            } else if meta.path.is_ident("handler")
+                || meta.path.is_ident("handler_with_state")
+                || meta.path.is_ident("async_handler")
+            {
+                Err(meta
+                    .error("an actor has no handler trait, its impl block handles the messages"))
+            } else {
+                config.parse_meta(&meta)
+            }
+        })
+        .parse2(attr)?;
+        config.check_derives()?;
+        Ok(config)
+    }
+
+    fn check_derives(&self) -> syn::Result<()> {
+        if !self.skip.debug
+            && let Some(debug) = self.derives.iter().find(|path| path.is_ident("Debug"))
+        {
+            return Err(syn::Error::new_spanned(
+                debug,
+                "the message enum already implements `Debug`, add `skip(debug)` to derive it instead",
+            ));
+        }
+        Ok(())
+    }
+
+    fn parse_meta(&mut self, meta: &ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("client") {
+            set_once(meta, &mut self.client)
+        } else if meta.path.is_ident("message") {
+            set_once(meta, &mut self.message)
+        } else if meta.path.is_ident("handler") {
+            set_once(meta, &mut self.handler)
+        } else if meta.path.is_ident("handler_with_state") {
+            set_once(meta, &mut self.handler_with_state)
+        } else if meta.path.is_ident("async_handler") {
+            set_once(meta, &mut self.async_handler)
+        } else if meta.path.is_ident("params") {
+            set_once(meta, &mut self.params)?;
+            let params = self.params.as_ref().unwrap();
+            let pattern = params.value();
+            if pattern.matches("{}").count() != 1
+                || syn::parse_str::<syn::Ident>(&pattern.replace("{}", "Variant")).is_err()
+            {
+                return Err(syn::Error::new_spanned(
+                    params,
+                    "expected a name pattern with exactly one `{}`, e.g. \"{}Params\"",
+                ));
+            }
+            Ok(())
+        } else if meta.path.is_ident("module") {
+            if self.module.is_some() {
+                return Err(meta.error("option is given more than once"));
+            }
+            self.module = Some(if meta.input.peek(syn::Token![=]) {
+                Some(meta.value()?.parse()?)
+            } else {
+                None
+            });
+            Ok(())
+        } else if meta.path.is_ident("batch") {
+            if self.batch {
+                return Err(meta.error("option is given more than once"));
+            }
+            self.batch = true;
+            Ok(())
+        } else if meta.path.is_ident("tokio") {
+            if self.tokio {
+                return Err(meta.error("option is given more than once"));
+            }
+            if self.backpressure.is_some() {
+                return Err(meta.error(TOKIO_BACKPRESSURE));
+            }
+            if self.skip.async_handler {
+                return Err(meta.error(TOKIO_SKIP_ASYNC_HANDLER));
+            }
+            self.tokio = true;
+            Ok(())
+        } else if meta.path.is_ident("sender") {
+            set_once(meta, &mut self.sender)
+        } else if meta.path.is_ident("backpressure") {
+            if self.backpressure.is_some() {
+                return Err(meta.error("option is given more than once"));
+            }
+            if self.tokio {
+                return Err(meta.error(TOKIO_BACKPRESSURE));
+            }
+            self.backpressure = Some(parse_backpressure(&meta.value()?.parse()?)?);
+            Ok(())
+        } else if meta.path.is_ident("derive") {
+            meta.parse_nested_meta(|meta| {
+                self.derives.push(meta.path);
+                Ok(())
+            })
+        } else if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|meta| {
+                let flag = if meta.path.is_ident("client") {
+                    &mut self.skip.client
+                } else if meta.path.is_ident("handler") {
+                    &mut self.skip.handler
+                } else if meta.path.is_ident("handler_with_state") {
+                    &mut self.skip.handler_with_state
+                } else if meta.path.is_ident("async_handler") {
+                    if self.tokio {
+                        return Err(meta.error(TOKIO_SKIP_ASYNC_HANDLER));
+                    }
+                    &mut self.skip.async_handler
+                } else if meta.path.is_ident("debug") {
+                    &mut self.skip.debug
+                } else {
+                    return Err(meta.error(
+                        "unknown artifact, expected `client`, `handler`, `handler_with_state`, `async_handler` or `debug`",
+                    ));
+                };
+                *flag = true;
+                Ok(())
+            })
+        } else {
+            Err(meta.error(
+                "unknown option, expected `client`, `message`, `handler`, `handler_with_state`, `async_handler`, `params`, `derive`, `skip`, `module`, `batch`, `backpressure`, `sender` or `tokio`",
+            ))
+        }
+    }
+
+    /// Options of the `#[protocol(...)]` attribute of a `#[derive(ChannelProtocol)]` enum, the
+    /// subset that applies to a hand-written enum.
+    pub fn parse_enum_attr(&mut self, attr: &syn::Attribute) -> syn::Result<()> {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("client")
+                || meta.path.is_ident("handler")
+                || meta.path.is_ident("handler_with_state")
+                || meta.path.is_ident("backpressure")
+                || meta.path.is_ident("sender")
+            {
+                self.parse_meta(&meta)
+            } else if meta.path.is_ident("skip") {
+                meta.parse_nested_meta(|meta| {
+                    let flag = if meta.path.is_ident("client") {
+                        &mut self.skip.client
+                    } else if meta.path.is_ident("handler") {
+                        &mut self.skip.handler
+                    } else if meta.path.is_ident("handler_with_state") {
+                        &mut self.skip.handler_with_state
+                    } else {
+                        return Err(meta.error(
+                            "unknown artifact, expected `client`, `handler` or `handler_with_state`",
+                        ));
+                    };
+                    *flag = true;
+                    Ok(())
+                })
+            } else {
+                Err(meta.error(
+                    "unknown option, expected `client`, `handler`, `handler_with_state`, `backpressure`, `sender` or `skip`",
+                ))
+            }
+        })
+    }
+}
+
+/// Error of a `backpressure` policy given to a `tokio` client.
+pub const TOKIO_BACKPRESSURE: &str =
+    "`tokio` clients send through an unbounded channel, they have no backpressure policy";
+
+/// Error of `skip(async_handler)` given to a `tokio` client.
+const TOKIO_SKIP_ASYNC_HANDLER: &str =
+    "`tokio` clients spawn the async handler with `Client::spawn`, it cannot be skipped";
+
+/// `Backpressure` variant named by `block`, `error`, `drop_newest` or `drop_oldest`.
+pub fn parse_backpressure(policy: &syn::Ident) -> syn::Result<syn::Ident> {
+    let variant = match policy.to_string().as_str() {
+        "block" => "Block",
+        "error" => "Error",
+        "drop_newest" => "DropNewest",
+        "drop_oldest" => "DropOldest",
+        _ => {
+            return Err(syn::Error::new_spanned(
+                policy,
+                "unknown backpressure policy, expected `block`, `error`, `drop_newest` or `drop_oldest`",
+            ));
+        }
+    };
+    Ok(syn::Ident::new(variant, policy.span()))
+}
+
+fn set_once<T: syn::parse::Parse>(meta: &ParseNestedMeta, slot: &mut Option<T>) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("option is given more than once"));
+    }
+    *slot = Some(meta.value()?.parse()?);
+    Ok(())
+}
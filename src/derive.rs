@@ -0,0 +1,192 @@
+use convert_case::{Case, Casing};
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use syn::punctuated::Punctuated;
+
+use crate::{
+    channel_protocol::{
+        self, Errors, MessageFields, Protocol, ProtocolKind, ProtocolMessage, ProtocolMessageFnArg,
+    },
+    config::ProtocolConfig,
+};
+
+impl TryFrom<syn::DeriveInput> for Protocol {
+    type Error = syn::Error;
+
+    fn try_from(input: syn::DeriveInput) -> syn::Result<Self> {
+        let syn::Data::Enum(data) = input.data else {
+            return Err(syn::Error::new_spanned(
+                input.ident,
+                "`ChannelProtocol` can only be derived for a message enum",
+            ));
+        };
+        let mut errors = Errors::default();
+
+        let mut config = ProtocolConfig {
+            message: Some(input.ident.clone()),
+            ..ProtocolConfig::default()
+        };
+        for attr in &input.attrs {
+            if attr.path().is_ident("protocol") {
+                errors.ok(config.parse_enum_attr(attr));
+            }
+        }
+        for param in &input.generics.params {
+            if !matches!(param, syn::GenericParam::Type(_)) {
+                errors.push(syn::Error::new_spanned(
+                    param,
+                    "only type parameters are supported on a protocol enum",
+                ));
+            }
+        }
+
+        let messages = data
+            .variants
+            .into_iter()
+            .filter_map(|variant| errors.ok(ProtocolMessage::try_from(variant)))
+            .collect();
+
+        // `CounterMessage` gives `CounterClient` and `HandleCounter`.
+        let name = input.ident.to_string();
+        let ident = match name.strip_suffix("Message") {
+            Some(base) if !base.is_empty() => format_ident!("{}", base),
+            _ => input.ident,
+        };
+
+        let protocol = Self {
+            docs: Vec::new(),
+            vis: input.vis,
+            ident,
+            generics: input.generics,
+            sub_protocols: Vec::new(),
+            messages,
+            config,
+            kind: ProtocolKind::Enum,
+        };
+        protocol.validate(&mut errors);
+
+        errors.finish()?;
+        Ok(protocol)
+    }
+}
+
+impl TryFrom<syn::Variant> for ProtocolMessage {
+    type Error = syn::Error;
+
+    fn try_from(variant: syn::Variant) -> syn::Result<Self> {
+        let mut docs = Vec::new();
+        let mut attrs = Vec::new();
+        // Other attributes belong to other derives and are left alone.
+        for attr in variant.attrs {
+            if attr.path().is_ident("doc") {
+                docs.push(attr);
+            } else if attr.path().is_ident("cfg") || attr.path().is_ident("deprecated") {
+                attrs.push(attr);
+            }
+        }
+
+        let field_count = variant.fields.len();
+        let mut reply = None;
+        let mut args = Punctuated::new();
+        for (index, field) in variant.fields.iter().enumerate() {
+            if let Some(ty) = reply_type(&field.ty)? {
+                if reply.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "a message can only have one reply sender",
+                    ));
+                }
+                if field.ident.is_none() && index + 1 != field_count {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "the reply sender must be the last field of the variant",
+                    ));
+                }
+                reply = Some((field.ident.clone(), ty.clone()));
+                continue;
+            }
+            args.push(ProtocolMessageFnArg {
+                docs: field
+                    .attrs
+                    .iter()
+                    .filter(|attr| attr.path().is_ident("doc"))
+                    .cloned()
+                    .collect(),
+                ident: field
+                    .ident
+                    .clone()
+                    .unwrap_or_else(|| format_ident!("arg{}", index)),
+                ty: field.ty.clone(),
+                client_ty: field.ty.clone(),
+                conversion: None,
+            });
+        }
+
+        let fields = match variant.fields {
+            syn::Fields::Unit => MessageFields::Unit,
+            syn::Fields::Named(_) => MessageFields::Named {
+                reply: reply.as_ref().and_then(|(ident, _)| ident.clone()),
+            },
+            syn::Fields::Unnamed(_) => MessageFields::Unnamed,
+        };
+        let output = match reply {
+            Some((_, ty)) => syn::parse_quote! { -> #ty },
+            None => syn::ReturnType::Default,
+        };
+
+        Ok(Self {
+            docs,
+            attrs,
+            asyncness: None,
+            ident: syn::Ident::new(
+                &variant.ident.to_string().to_case(Case::Snake),
+                variant.ident.span(),
+            ),
+            variant: variant.ident,
+            fields,
+            args,
+            output,
+            default: None,
+            timeout: None,
+            backpressure: None,
+        })
+    }
+}
+
+/// `T` of a `oneshot::Sender<T>` reply field. Senders of other `oneshot` modules, such as
+/// `tokio::sync::oneshot`, are rejected since the generated code replies with the `oneshot`
+/// crate.
+fn reply_type(ty: &syn::Type) -> syn::Result<Option<&syn::Type>> {
+    let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+        return Ok(None);
+    };
+    let mut segments = path.segments.iter().rev();
+    let (Some(sender), Some(module)) = (segments.next(), segments.next()) else {
+        return Ok(None);
+    };
+    if sender.ident != "Sender" || module.ident != "oneshot" {
+        return Ok(None);
+    }
+    if path.segments.len() != 2 {
+        return Err(syn::Error::new_spanned(
+            path,
+            "reply senders must be `oneshot::Sender<T>` from the `oneshot` crate",
+        ));
+    }
+    let syn::PathArguments::AngleBracketed(arguments) = &sender.arguments else {
+        return Ok(None);
+    };
+    match arguments.args.iter().collect::<Vec<_>>()[..] {
+        [syn::GenericArgument::Type(ty)] => Ok(Some(ty)),
+        _ => Ok(None),
+    }
+}
+
+pub fn build(input: TokenStream) -> TokenStream {
+    let protocol = match syn::parse2::<syn::DeriveInput>(input).and_then(Protocol::try_from) {
+        Ok(protocol) => protocol,
+        Err(error) => return error.to_compile_error(),
+    };
+
+    channel_protocol::render(&protocol)
+}
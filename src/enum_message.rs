@@ -9,17 +9,18 @@ use syn::punctuated::Punctuated;
 use syn::token::Comma;
 
 use crate::channel_protocol::ProtocolMessageFnArg;
-use crate::channel_protocol::{Protocol, ProtocolMessage};
+use crate::channel_protocol::{Protocol, ProtocolKind, ProtocolMessage};
 use crate::render::message::MessageSignatureKind;
 
 struct MessageStructDefinitionRenderer<'a> {
+    protocol: &'a Protocol,
     message: &'a ProtocolMessage,
 }
 
-fn arg_to_field(arg: &ProtocolMessageFnArg) -> Field {
+fn arg_to_field(vis: &syn::Visibility, arg: &ProtocolMessageFnArg) -> Field {
     Field {
-        attrs: Vec::new(),
-        vis: syn::Visibility::Inherited,
+        attrs: arg.docs.clone(),
+        vis: vis.clone(),
         mutability: FieldMutability::None,
         ident: Some(arg.ident.clone()),
         colon_token: Some(syn::token::Colon::default()),
@@ -33,17 +34,48 @@ impl ToTokens for MessageStructDefinitionRenderer<'_> {
             return;
         }
 
-        let struct_name = self.message.struct_ident();
-        let fields = self
+        let vis = &self.protocol.item_vis();
+        let struct_name = self.protocol.message_struct_ident(self.message);
+        let attrs = &self.message.attrs;
+        // The message enum prints its arguments through the `Debug` of this struct, unless
+        // `skip(debug)` leaves the enum to the derives of the user.
+        let debug = (!self.protocol.config.skip.debug).then(|| quote! { Debug });
+        let derives = debug
+            .into_iter()
+            .chain(
+                self.protocol
+                    .config
+                    .derives
+                    .iter()
+                    .map(ToTokens::to_token_stream),
+            )
+            .collect::<Vec<_>>();
+        let derive = (!derives.is_empty()).then(|| {
+            quote! {
+                #[derive(#(#derives),*)]
+            }
+        });
+        let mut fields = self
             .message
             .args
             .iter()
-            .map(arg_to_field)
+            .map(|arg| arg_to_field(vis, arg))
             .collect::<Punctuated<_, Comma>>();
 
+        let phantom_params = self.protocol.phantom_type_params(self.message);
+        if !phantom_params.is_empty() {
+            fields.push(syn::parse_quote! {
+                #vis _marker: std::marker::PhantomData<fn() -> (#(#phantom_params,)*)>
+            });
+        }
+
+        let generics = &self.protocol.generics;
+        let where_clause = &generics.where_clause;
+
         tokens.extend(quote! {
-            #[derive(Debug)]
-            struct #struct_name {
+            #(#attrs)*
+            #derive
+            #vis struct #struct_name #generics #where_clause {
                 #fields
             }
         });
@@ -55,6 +87,7 @@ struct MessageDebugImplRenderer<'a> {
 }
 
 struct MessageMatchArmDebugImplRenderer<'a> {
+    protocol: &'a Protocol,
     message: &'a ProtocolMessage,
     enum_ident: &'a Ident,
 }
@@ -63,8 +96,10 @@ impl ToTokens for MessageMatchArmDebugImplRenderer<'_> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let message = self.message;
         let ident = &message.ident;
-        let variant_ident = message.pascal_case_ident();
+        let variant_ident = message.variant.clone();
         let enum_ident = self.enum_ident;
+        let rest =
+            (!self.protocol.phantom_type_params(message).is_empty()).then(|| quote! { , .. });
 
         let match_arm = match self.message.signature_kind() {
             MessageSignatureKind::None => {
@@ -79,7 +114,7 @@ impl ToTokens for MessageMatchArmDebugImplRenderer<'_> {
                 }
             }
             MessageSignatureKind::OnlyParam => {
-                let struct_param = message.struct_ident();
+                let struct_param = self.protocol.message_struct_path(message);
 
                 let arg_names = message
                     .args
@@ -98,13 +133,13 @@ impl ToTokens for MessageMatchArmDebugImplRenderer<'_> {
                 let format_str = format!("{{}}({punctuated_arg_format})");
 
                 quote! {
-                    #enum_ident::#variant_ident(#struct_param { #arg_names }) => {
+                    #enum_ident::#variant_ident(#struct_param { #arg_names #rest }) => {
                         write!(f, #format_str, stringify!(#ident), #arg_names)
                     },
                 }
             }
             MessageSignatureKind::ParamReturn => {
-                let struct_param = message.struct_ident();
+                let struct_param = self.protocol.message_struct_path(message);
                 let ret = &message.output;
 
                 let arg_names = message
@@ -124,34 +159,85 @@ impl ToTokens for MessageMatchArmDebugImplRenderer<'_> {
                 let format_str = format!("{{}}({punctuated_arg_format}) {{}}");
 
                 quote! {
-                    #enum_ident::#variant_ident(#struct_param { #arg_names }, _) => {
+                    #enum_ident::#variant_ident(#struct_param { #arg_names #rest }, _) => {
                         write!(f, #format_str, stringify!(#ident), #arg_names, stringify!(#ret))
                     },
                 }
             }
         };
 
-        tokens.extend(match_arm);
+        let cfg_attrs = message.cfg_attrs();
+        tokens.extend(quote! {
+            #(#cfg_attrs)*
+            #match_arm
+        });
     }
 }
 
 impl ToTokens for MessageDebugImplRenderer<'_> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let message_enum_ident = self.protocol.message_enum_ident();
+        let message_enum_type = self.protocol.message_enum_type();
         let match_arms =
             self.protocol
                 .messages
                 .iter()
                 .map(|message| MessageMatchArmDebugImplRenderer {
+                    protocol: self.protocol,
                     message,
                     enum_ident: &message_enum_ident,
                 });
 
+        let sub_protocol_arms = self.protocol.sub_protocols.iter().map(|sub_protocol| {
+            let variant_ident = sub_protocol.variant_ident();
+            quote! {
+                #message_enum_ident::#variant_ident(message) => std::fmt::Debug::fmt(message, f),
+            }
+        });
+
+        let batch_arm = self.protocol.config.batch.then(|| {
+            quote! {
+                #message_enum_ident::Batch(messages) => {
+                    f.debug_tuple("batch").field(messages).finish()
+                }
+            }
+        });
+
+        let mut generics = self.protocol.generics.clone();
+        for sub_protocol in &self.protocol.sub_protocols {
+            let path = sub_protocol.message_enum_path();
+            let ty = syn::parse_quote!(#path);
+            if !self.protocol.mentioned_type_params(&ty).is_empty() {
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse_quote! { #ty: std::fmt::Debug });
+            }
+        }
+        for arg in self
+            .protocol
+            .messages
+            .iter()
+            .flat_map(|message| &message.args)
+        {
+            if !self.protocol.mentioned_type_params(&arg.ty).is_empty() {
+                let ty = &arg.ty;
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse_quote! { #ty: std::fmt::Debug });
+            }
+        }
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
         tokens.extend(quote! {
-            impl std::fmt::Debug for #message_enum_ident {
+            #[allow(deprecated)]
+            impl #impl_generics std::fmt::Debug for #message_enum_type #where_clause {
                 fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                     match self {
+                        #(#sub_protocol_arms)*
                         #(#match_arms)*
+                        #batch_arm
                     }
                 }
             }
@@ -165,67 +251,138 @@ struct MessageEnumDefinitionRenderer<'a> {
 
 impl ToTokens for MessageEnumDefinitionRenderer<'_> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        if self.protocol.messages.is_empty() {
+        if self.protocol.messages.is_empty() && self.protocol.sub_protocols.is_empty() {
             return;
         }
 
-        let variants = self
-            .protocol
-            .messages
-            .iter()
-            .map(|m| {
-                let variant_name = m.pascal_case_ident();
-
-                let ret_val = match &m.output {
-                    ReturnType::Default => None,
-                    ReturnType::Type(_, ty) => Some(quote! {
-                        oneshot::Sender<#ty>
-                    }),
-                };
-
-                if m.args.is_empty() {
-                    ret_val.map_or_else(
-                        || {
-                            quote! {
-                                #variant_name
-                            }
-                        },
-                        |ret_val| {
-                            quote! {
-                                #variant_name(#ret_val)
-                            }
-                        },
-                    )
-                } else {
-                    let struct_name = m.struct_ident();
-                    quote! {
-                        #variant_name(#struct_name, #ret_val)
-                    }
+        let sub_protocol_variants = self.protocol.sub_protocols.iter().map(|sub_protocol| {
+            let variant_name = sub_protocol.variant_ident();
+            let message_enum_path = sub_protocol.message_enum_path();
+            quote! {
+                #variant_name(#message_enum_path)
+            }
+        });
+        let variants = self.protocol.messages.iter().map(|m| {
+            let variant_name = m.variant.clone();
+            let ProtocolMessage { docs, attrs, .. } = m;
+
+            let ret_val = match &m.output {
+                ReturnType::Default => None,
+                ReturnType::Type(_, ty) => Some(quote! {
+                    oneshot::Sender<#ty>
+                }),
+            };
+
+            let variant = if m.args.is_empty() {
+                ret_val.map_or_else(
+                    || {
+                        quote! {
+                            #variant_name
+                        }
+                    },
+                    |ret_val| {
+                        quote! {
+                            #variant_name(#ret_val)
+                        }
+                    },
+                )
+            } else {
+                let struct_type = self.protocol.message_struct_type(m);
+                quote! {
+                    #variant_name(#struct_type, #ret_val)
                 }
-            })
+            };
+
+            quote! {
+                #(#docs)*
+                #(#attrs)*
+                #variant
+            }
+        });
+        let batch_variant = self.protocol.config.batch.then(|| {
+            let client_struct_name = self.protocol.client_ident();
+            let doc = format!(
+                "Messages dispatched back to back, sent by [`{client_struct_name}::batch`]."
+            );
+            quote! {
+                #[doc = #doc]
+                Batch(Vec<Self>)
+            }
+        });
+        let variants = sub_protocol_variants
+            .chain(variants)
+            .chain(batch_variant)
             .collect::<Punctuated<_, Comma>>();
 
-        let vis = &self.protocol.vis;
+        let vis = self.protocol.item_vis();
         let message_enum_name = self.protocol.message_enum_ident();
+        let generics = &self.protocol.generics;
+        let where_clause = &generics.where_clause;
+        let derives = &self.protocol.config.derives;
+        let derive = (!derives.is_empty()).then(|| {
+            quote! {
+                #[derive(#(#derives),*)]
+            }
+        });
+        let (impl_generics, ty_generics, _) = generics.split_for_impl();
+        let from_impls = self.protocol.sub_protocols.iter().map(|sub_protocol| {
+            let variant_name = sub_protocol.variant_ident();
+            let message_enum_path = sub_protocol.message_enum_path();
+            quote! {
+                impl #impl_generics From<#message_enum_path> for #message_enum_name #ty_generics #where_clause {
+                    fn from(message: #message_enum_path) -> Self {
+                        Self::#variant_name(message)
+                    }
+                }
+            }
+        });
 
         tokens.extend(quote! {
-            #vis enum #message_enum_name {
+            #derive
+            #[allow(deprecated)]
+            #vis enum #message_enum_name #generics #where_clause {
                 #variants
             }
+
+            #(#from_impls)*
         });
     }
 }
 
 pub fn build(protocol: &Protocol) -> TokenStream {
+    // A hand-written enum is already there.
+    if matches!(protocol.kind, ProtocolKind::Enum) {
+        return TokenStream::new();
+    }
+
     let message_enum = MessageEnumDefinitionRenderer { protocol };
     let message_structs = protocol
         .messages
         .iter()
-        .map(|m| MessageStructDefinitionRenderer { message: m });
-    let enum_debug_impl = MessageDebugImplRenderer { protocol };
+        .map(|m| MessageStructDefinitionRenderer {
+            protocol,
+            message: m,
+        });
+    let enum_debug_impl =
+        (!protocol.config.skip.debug).then_some(MessageDebugImplRenderer { protocol });
+
+    let message_structs = if protocol.config.module.is_some() {
+        quote! {
+            pub mod params {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #(#message_structs)*
+            }
+        }
+    } else {
+        quote! {
+            #(#message_structs)*
+        }
+    };
 
     quote! {
-        #(#message_structs)*
+        #message_structs
         #message_enum
         #enum_debug_impl
     }
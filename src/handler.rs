@@ -1,12 +1,8 @@
-use convert_case::Casing;
 use proc_macro2::TokenStream;
-use quote::{ToTokens, format_ident, quote};
-use syn::{Ident, punctuated::Punctuated, token::Comma};
+use quote::{ToTokens, quote};
+use syn::ReturnType;
 
-use crate::{
-    channel_protocol::{Protocol, ProtocolMessage},
-    render::message::MessageSignatureKind,
-};
+use crate::channel_protocol::{Protocol, ProtocolKind, ProtocolMessage};
 
 struct HandleTraitRenderer<'a> {
     protocol: &'a Protocol,
@@ -14,61 +10,140 @@ struct HandleTraitRenderer<'a> {
 
 impl ToTokens for HandleTraitRenderer<'_> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let Protocol { vis, messages, .. } = self.protocol;
-        let handler_ident_with_state = format_ident!("Handle{}WithState", self.protocol.ident);
-        let handler_ident_without_state = format_ident!("Handle{}", self.protocol.ident);
+        let Protocol {
+            messages, config, ..
+        } = self.protocol;
+        let vis = self.protocol.item_vis();
+        let handler_ident_with_state = self.protocol.handler_with_state_ident();
+        let handler_ident_without_state = self.protocol.handler_ident();
 
         let messages_with_state = messages
             .iter()
             .map(|message| HandleProtocolMessageRenderer {
+                protocol: self.protocol,
                 message,
                 with_state: true,
+                is_async: false,
             })
             .collect::<Vec<_>>();
 
         let messages_without_state = messages
             .iter()
             .map(|message| HandleProtocolMessageRenderer {
+                protocol: self.protocol,
                 message,
                 with_state: false,
+                is_async: false,
             })
             .collect::<Vec<_>>();
 
-        let dispatch_method_with_state = DispatchMethodRenderer {
-            protocol: self.protocol,
-            with_state: true,
+        let generics = &self.protocol.generics;
+        let where_clause = &generics.where_clause;
+        let sub_protocols = &self.protocol.sub_protocols;
+        let reply_dropped = quote! {
+            /// Called when the reply to `method` could not be delivered because its caller
+            /// stopped waiting for it. Does nothing by default.
+            fn reply_dropped(&mut self, _method: &'static str) {}
         };
 
-        let dispatch_method_without_state = DispatchMethodRenderer {
-            protocol: self.protocol,
-            with_state: false,
-        };
+        if !config.skip.handler_with_state {
+            let state_type = self.protocol.state_type_ident();
+            let mut generics_with_state = generics.clone();
+            // The messages of a batch each get a copy of the state.
+            generics_with_state.params.push(if config.batch {
+                syn::parse_quote! { #state_type: Clone = () }
+            } else {
+                syn::parse_quote! { #state_type = () }
+            });
+            let sub_handlers = sub_protocols
+                .iter()
+                .map(|sub_protocol| sub_protocol.handler_with_state_path(&state_type));
+            let colon = (!sub_protocols.is_empty()).then(|| quote! { : });
+            let dispatch_impl = DispatchImplRenderer {
+                protocol: self.protocol,
+                with_state: true,
+            };
 
-        tokens.extend(quote! {
-            #vis trait #handler_ident_with_state <S = ()> {
-                #( #messages_with_state )*
-                #dispatch_method_with_state
-            }
+            tokens.extend(quote! {
+                #vis trait #handler_ident_with_state #generics_with_state #colon #(#sub_handlers)+* #where_clause {
+                    #( #messages_with_state )*
+                    #reply_dropped
+                }
 
-            #vis trait #handler_ident_without_state {
-                #( #messages_without_state )*
-                #dispatch_method_without_state
-            }
-        });
+                #dispatch_impl
+            });
+        }
+
+        if !config.skip.handler {
+            let sub_handlers = sub_protocols
+                .iter()
+                .map(|sub_protocol| sub_protocol.handler_path());
+            let colon = (!sub_protocols.is_empty()).then(|| quote! { : });
+            let dispatch_impl = DispatchImplRenderer {
+                protocol: self.protocol,
+                with_state: false,
+            };
+
+            tokens.extend(quote! {
+                #vis trait #handler_ident_without_state #generics #colon #(#sub_handlers)+* #where_clause {
+                    #( #messages_without_state )*
+                    #reply_dropped
+                }
+
+                #dispatch_impl
+            });
+        }
+
+        if self.protocol.is_async() && !config.skip.async_handler {
+            let async_handler_ident = self.protocol.async_handler_ident();
+            let async_messages = messages
+                .iter()
+                .map(|message| HandleProtocolMessageRenderer {
+                    protocol: self.protocol,
+                    message,
+                    with_state: false,
+                    is_async: true,
+                });
+            let async_dispatch_method = AsyncDispatchMethodRenderer {
+                protocol: self.protocol,
+            };
+
+            // Composed protocols may not have an async handler, their messages are handled by
+            // their stateless handler.
+            let sub_handlers = sub_protocols
+                .iter()
+                .map(|sub_protocol| sub_protocol.handler_path());
+
+            // Handler futures are `Send` so that the dispatch loop can run in a spawned task.
+            tokens.extend(quote! {
+                #vis trait #async_handler_ident #generics: Send #(+ #sub_handlers)* #where_clause {
+                    #( #async_messages )*
+                    #reply_dropped
+                    #async_dispatch_method
+                }
+            });
+        }
     }
 }
 
 struct HandleProtocolMessageRenderer<'a> {
+    protocol: &'a Protocol,
     message: &'a ProtocolMessage,
     with_state: bool,
+    is_async: bool,
 }
 
 impl ToTokens for HandleProtocolMessageRenderer<'_> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let ProtocolMessage {
+            docs,
+            attrs,
+            asyncness,
             ident,
             args,
             output,
+            default,
+            ..
         } = self.message;
 
         let args = if args.is_empty() {
@@ -77,172 +152,411 @@ impl ToTokens for HandleProtocolMessageRenderer<'_> {
             quote! { , #args }
         };
 
-        tokens.extend(if self.with_state {
+        tokens.extend(if self.is_async {
+            let output = match output {
+                ReturnType::Default => quote! { () },
+                ReturnType::Type(_, ty) => quote! { #ty },
+            };
+            // Default bodies of `async` methods become the default of the async handler.
+            let body = match default {
+                Some(default) if asyncness.is_some() => quote! { { async move #default } },
+                _ => quote! { ; },
+            };
+            quote! {
+                #(#docs)*
+                #(#attrs)*
+                fn #ident(&mut self #args) -> impl std::future::Future<Output = #output> + Send #body
+            }
+        } else if self.with_state {
+            let state_type = self.protocol.state_type_ident();
             quote! {
-                fn #ident(&mut self #args, state: S) #output;
+                #(#docs)*
+                #(#attrs)*
+                fn #ident(&mut self #args, state: #state_type) #output;
             }
         } else {
+            // Default bodies of other methods become the default of the stateless handler.
+            let body = match default {
+                Some(default) if asyncness.is_none() => quote! { #default },
+                _ => quote! { ; },
+            };
             quote! {
-                fn #ident(&mut self #args) #output;
+                #(#docs)*
+                #(#attrs)*
+                fn #ident(&mut self #args) #output #body
             }
         });
     }
 }
 
-struct DispatchMethodRenderer<'a> {
+/// `HandledBy` or `HandledWithStateBy` implementation of the message enum for every handler of
+/// the stateless or the stateful trait, behind `Dispatch::dispatch` and
+/// `Dispatch::dispatch_with_state`.
+struct DispatchImplRenderer<'a> {
     protocol: &'a Protocol,
     with_state: bool,
 }
 
-impl ToTokens for DispatchMethodRenderer<'_> {
+impl ToTokens for DispatchImplRenderer<'_> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        let Protocol { messages, .. } = self.protocol;
+        let Protocol {
+            messages, config, ..
+        } = self.protocol;
         let enum_message_ident = self.protocol.message_enum_ident();
+        let enum_message_type = self.protocol.message_enum_type();
+        let state_type = self.protocol.state_type_ident();
+        let handler_type = self.protocol.handler_type_ident();
+        let state = self.protocol.state_arg();
+
+        let handler = if self.with_state {
+            let ident = self.protocol.handler_with_state_ident();
+            let params = self
+                .protocol
+                .generics
+                .type_params()
+                .map(|param| &param.ident);
+            quote! { #ident<#(#params,)* #state_type> }
+        } else {
+            let ident = self.protocol.handler_ident();
+            let (_, ty_generics, _) = self.protocol.generics.split_for_impl();
+            quote! { #ident #ty_generics }
+        };
+        let callee = quote! { <#handler_type as #handler> };
+        let receiver = quote! { handler };
+        let reply_dropped = quote! { reply_dropped };
 
         let dispatch_arms = messages.iter().map(|message| DispatchMessageRenderer {
+            protocol: self.protocol,
             message,
-            enum_message_ident: &enum_message_ident,
+            callee: &callee,
+            receiver: &receiver,
+            reply_dropped: Some(&reply_dropped),
             with_state: self.with_state,
+            is_async: false,
         });
 
+        // Messages of composed protocols are dispatched by their own implementation, with the
+        // `reply_dropped` of this handler.
+        let sub_protocol_arms = self.protocol.sub_protocols.iter().map(|sub_protocol| {
+            let variant_ident = sub_protocol.variant_ident();
+            let dispatch = if self.with_state {
+                quote! {
+                    ::channel_protocol_runtime::HandledWithStateBy::<#handler_type, #state_type>::dispatch_with_state_reporting(
+                        message,
+                        handler,
+                        #state,
+                        reply_dropped,
+                    )
+                }
+            } else {
+                quote! {
+                    ::channel_protocol_runtime::HandledBy::<#handler_type>::dispatch_reporting(
+                        message,
+                        handler,
+                        reply_dropped,
+                    )
+                }
+            };
+            quote! {
+                #enum_message_ident::#variant_ident(message) => #dispatch,
+            }
+        });
+        let dispatch_arms = sub_protocol_arms
+            .chain(dispatch_arms.map(|arm| arm.into_token_stream()))
+            .collect::<Vec<_>>();
+        let dispatch_body = dispatch_body(self.protocol, &dispatch_arms);
+
+        let mut generics = self.protocol.generics.clone();
+        if self.with_state {
+            generics.params.push(if config.batch {
+                syn::parse_quote! { #state_type: Clone }
+            } else {
+                syn::parse_quote! { #state_type }
+            });
+        }
+        generics
+            .params
+            .push(syn::parse_quote! { #handler_type: #handler + ?Sized });
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+
         tokens.extend(if self.with_state {
             quote! {
-                fn _dispatch_with_state(
-                    &mut self,
-                    message: #enum_message_ident,
-                    state: S,
-                ) {
-                    match message {
-                        #( #dispatch_arms )*
+                impl #impl_generics ::channel_protocol_runtime::HandledWithStateBy<#handler_type, #state_type>
+                    for #enum_message_type #where_clause
+                {
+                    fn dispatch_with_state_to(self, handler: &mut #handler_type, state: #state_type) {
+                        ::channel_protocol_runtime::HandledWithStateBy::<#handler_type, #state_type>::dispatch_with_state_reporting(
+                            self,
+                            handler,
+                            state,
+                            #callee::reply_dropped,
+                        );
                     }
-                }
 
-                fn dispatch_with_state(
-                    &mut self,
-                    message: #enum_message_ident,
-                    state: S,
-                ) {
-                    self._dispatch_with_state(message, state);
+                    #[allow(deprecated)]
+                    fn dispatch_with_state_reporting(
+                        self,
+                        handler: &mut #handler_type,
+                        state: #state_type,
+                        reply_dropped: fn(&mut #handler_type, &'static str),
+                    ) {
+                        let message = self;
+                        #dispatch_body
+                    }
                 }
             }
         } else {
             quote! {
-                fn _dispatch(
-                    &mut self,
-                    message: #enum_message_ident,
-                ) {
-                    match message {
-                        #( #dispatch_arms )*
+                impl #impl_generics ::channel_protocol_runtime::HandledBy<#handler_type>
+                    for #enum_message_type #where_clause
+                {
+                    fn dispatch_to(self, handler: &mut #handler_type) {
+                        ::channel_protocol_runtime::HandledBy::<#handler_type>::dispatch_reporting(
+                            self,
+                            handler,
+                            #callee::reply_dropped,
+                        );
+                    }
+
+                    #[allow(deprecated)]
+                    fn dispatch_reporting(
+                        self,
+                        handler: &mut #handler_type,
+                        reply_dropped: fn(&mut #handler_type, &'static str),
+                    ) {
+                        let message = self;
+                        #dispatch_body
                     }
                 }
+            }
+        });
+    }
+}
+
+/// `dispatch` of the async handler, awaiting the handler methods.
+struct AsyncDispatchMethodRenderer<'a> {
+    protocol: &'a Protocol,
+}
+
+impl ToTokens for AsyncDispatchMethodRenderer<'_> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Protocol { messages, .. } = self.protocol;
+        let enum_message_ident = self.protocol.message_enum_ident();
+        let enum_message_type = self.protocol.message_enum_type();
 
-                fn dispatch(
-                    &mut self,
-                    message: #enum_message_ident,
-                ) {
-                    self._dispatch(message);
+        // Handler methods are called through the trait, composed handlers share method names.
+        let ident = self.protocol.async_handler_ident();
+        let (_, ty_generics, _) = self.protocol.generics.split_for_impl();
+        let callee = quote! { <Self as #ident #ty_generics> };
+        let receiver = quote! { self };
+        let reply_dropped = quote! { #callee::reply_dropped };
+
+        let dispatch_arms = messages.iter().map(|message| DispatchMessageRenderer {
+            protocol: self.protocol,
+            message,
+            callee: &callee,
+            receiver: &receiver,
+            reply_dropped: Some(&reply_dropped),
+            with_state: false,
+            is_async: true,
+        });
+
+        // Messages of composed protocols are dispatched by their stateless handler, with the
+        // `reply_dropped` of this handler.
+        let sub_protocol_arms = self.protocol.sub_protocols.iter().map(|sub_protocol| {
+            let variant_ident = sub_protocol.variant_ident();
+            quote! {
+                #enum_message_ident::#variant_ident(message) => {
+                    ::channel_protocol_runtime::HandledBy::<Self>::dispatch_reporting(
+                        message,
+                        self,
+                        #reply_dropped,
+                    )
                 }
             }
         });
+        let dispatch_arms = sub_protocol_arms
+            .chain(dispatch_arms.map(|arm| arm.into_token_stream()))
+            .collect::<Vec<_>>();
+        let dispatch_body = dispatch_body(self.protocol, &dispatch_arms);
+
+        let send_bounds = self.protocol.generics.type_params().map(|param| {
+            let ident = &param.ident;
+            quote! { #ident: Send }
+        });
+        let where_clause = quote! { where #(#send_bounds,)* };
+        tokens.extend(quote! {
+            #[allow(deprecated)]
+            fn _dispatch(
+                &mut self,
+                message: #enum_message_type,
+            ) -> impl std::future::Future<Output = ()> + Send #where_clause {
+                async move {
+                    #dispatch_body
+                }
+            }
+
+            fn dispatch(
+                &mut self,
+                message: #enum_message_type,
+            ) -> impl std::future::Future<Output = ()> + Send #where_clause {
+                #callee::_dispatch(self, message)
+            }
+        });
     }
 }
 
 struct DispatchMessageRenderer<'a, 'b> {
+    protocol: &'a Protocol,
     message: &'a ProtocolMessage,
-    enum_message_ident: &'b Ident,
+    /// `Self` or `<H as Trait>`, prefix of the handler method calls.
+    callee: &'b TokenStream,
+    /// Expression of the handler the methods are called on.
+    receiver: &'b TokenStream,
+    /// Hook called with the method name when its caller stopped waiting for the reply.
+    reply_dropped: Option<&'b TokenStream>,
     with_state: bool,
+    is_async: bool,
 }
 
 impl ToTokens for DispatchMessageRenderer<'_, '_> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let ProtocolMessage { ident, args, .. } = self.message;
-        let enum_message_ident = self.enum_message_ident;
-        let message_variant_ident =
-            quote::format_ident!("{}", ident.to_string().to_case(convert_case::Case::Pascal));
-
-        match self.message.signature_kind() {
-            MessageSignatureKind::None => {
-                tokens.extend(if self.with_state {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident => {
-                            self.#ident(state);
-                        }
-                    }
-                } else {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident => {
-                            self.#ident();
-                        }
-                    }
-                });
+        let callee = self.callee;
+        let receiver = self.receiver;
+        let pattern = self.protocol.message_pattern(self.message);
+        let arg_idents = args.iter().map(|arg| &arg.ident);
+        let state = self.with_state.then(|| self.protocol.state_arg());
+        let await_call = self.is_async.then(|| quote! { .await });
+        let call = quote! {
+            #callee::#ident(#receiver, #(#arg_idents,)* #state) #await_call
+        };
+
+        // A caller that stopped waiting must not take the handler down.
+        let send_reply = match self.reply_dropped {
+            Some(reply_dropped) => quote! {
+                if tx.send(ret).is_err() {
+                    #reply_dropped(#receiver, stringify!(#ident));
+                }
+            },
+            None => quote! {
+                let _ = tx.send(ret);
+            },
+        };
+
+        let cfg_attrs = self.message.cfg_attrs();
+        tokens.extend(if self.message.has_reply() {
+            quote! {
+                #(#cfg_attrs)*
+                #pattern => {
+                    let ret = #call;
+                    #send_reply
+                }
             }
-            MessageSignatureKind::OnlyReturn => {
-                tokens.extend(if self.with_state {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident(tx) => {
-                            let ret = self.#ident(state);
-                            tx.send(ret).unwrap();
-                        }
-                    }
-                } else {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident(tx) => {
-                            let ret = self.#ident();
-                            tx.send(ret).unwrap();
-                        }
-                    }
-                });
+        } else {
+            quote! {
+                #(#cfg_attrs)*
+                #pattern => {
+                    #call;
+                }
             }
-            MessageSignatureKind::OnlyParam => {
-                let arg_idents = args
-                    .iter()
-                    .map(|arg| &arg.ident)
-                    .collect::<Punctuated<_, Comma>>();
-                let message_struct_ident = self.message.struct_ident();
-
-                tokens.extend(if self.with_state {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident(#message_struct_ident { #arg_idents }) => {
-                            self.#ident(#arg_idents, state);
-                        }
-                    }
-                } else {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident(#message_struct_ident { #arg_idents }) => {
-                            self.#ident(#arg_idents);
-                        }
-                    }
-                });
+        });
+    }
+}
+
+/// Inherent `dispatch` of an actor, calling its own methods.
+struct ActorDispatchRenderer<'a> {
+    protocol: &'a Protocol,
+    self_ty: &'a syn::Type,
+    reply_dropped: Option<&'a syn::Ident>,
+}
+
+impl ToTokens for ActorDispatchRenderer<'_> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let vis = self.protocol.item_vis();
+        let self_ty = self.self_ty;
+        let enum_message_type = self.protocol.message_enum_type();
+        let is_async = self.protocol.is_async();
+        let asyncness = is_async.then(|| quote! { async });
+        let callee = quote! { Self };
+        let receiver = quote! { self };
+        let reply_dropped = self
+            .reply_dropped
+            .map(|reply_dropped| quote! { Self::#reply_dropped });
+        let dispatch_arms = self
+            .protocol
+            .messages
+            .iter()
+            .map(|message| DispatchMessageRenderer {
+                protocol: self.protocol,
+                message,
+                callee: &callee,
+                receiver: &receiver,
+                reply_dropped: reply_dropped.as_ref(),
+                with_state: false,
+                // Only the `async` methods of the actor return a future.
+                is_async: message.asyncness.is_some(),
+            })
+            .map(|arm| arm.into_token_stream())
+            .collect::<Vec<_>>();
+        let dispatch_body = dispatch_body(self.protocol, &dispatch_arms);
+
+        tokens.extend(quote! {
+            #[allow(deprecated)]
+            impl #self_ty {
+                #vis #asyncness fn dispatch(&mut self, message: #enum_message_type) {
+                    #dispatch_body
+                }
             }
-            MessageSignatureKind::ParamReturn => {
-                let arg_idents = args
-                    .iter()
-                    .map(|arg| &arg.ident)
-                    .collect::<Punctuated<_, Comma>>();
-                let message_struct_ident = self.message.struct_ident();
-                tokens.extend(if self.with_state {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident(#message_struct_ident { #arg_idents }, tx) => {
-                            let ret = self.#ident(#arg_idents, state);
-                            tx.send(ret).unwrap();
-                        }
-                    }
-                } else {
-                    quote! {
-                        #enum_message_ident::#message_variant_ident(#message_struct_ident { #arg_idents }, tx) => {
-                            let ret = self.#ident(#arg_idents);
-                            tx.send(ret).unwrap();
-                        }
-                    }
-                });
+        });
+    }
+}
+
+/// Body of a dispatch method, matching `message` against `arms`. The messages of a batch are
+/// matched in turn by the same arms, nested batches included, without recursing so that async
+/// dispatch needs no boxing.
+fn dispatch_body(protocol: &Protocol, arms: &[TokenStream]) -> TokenStream {
+    if !protocol.config.batch {
+        return quote! {
+            match message {
+                #( #arms )*
             }
-        }
+        };
+    }
 
-        tokens.extend(quote! {});
+    let enum_message_ident = protocol.message_enum_ident();
+    quote! {
+        let mut message = message;
+        let mut batch = Vec::new();
+        loop {
+            match message {
+                #enum_message_ident::Batch(messages) => batch.extend(messages.into_iter().rev()),
+                #( #arms )*
+            }
+            match batch.pop() {
+                Some(next) => message = next,
+                None => break,
+            }
+        }
     }
 }
 
 pub fn build(protocol: &Protocol) -> TokenStream {
+    if let ProtocolKind::Actor {
+        self_ty,
+        reply_dropped,
+    } = &protocol.kind
+    {
+        let actor_dispatch = ActorDispatchRenderer {
+            protocol,
+            self_ty,
+            reply_dropped: reply_dropped.as_ref(),
+        };
+        return quote! {
+            #actor_dispatch
+        };
+    }
+
     let handle_trait = HandleTraitRenderer { protocol };
     quote! {
         #handle_trait
@@ -7,15 +7,151 @@
 //! ```
 #![doc = include_str!("../examples/sync.rs")]
 //! ```
+mod actor;
 mod channel_protocol;
 mod client;
+mod config;
+mod derive;
 mod enum_message;
 mod handler;
+mod protocol_trait;
 mod render;
 
 use proc_macro::TokenStream;
 /// Expect a trait definition as input and generate a channel protocol based on it.
+///
+/// The trait itself is kept, with `&self` receivers added to its methods, and implemented by the
+/// generated client. Methods may declare a `&self` or `&mut self` receiver, and a default body
+/// becomes the default implementation of the method in `Handle{Trait}`.
+///
+/// Methods returning `Result<T, E>` return `Result<T, CallError<E>>` from the client, where
+/// `CallError` from the `channel-protocol-runtime` crate also reports a disconnected or panicked
+/// handler instead of panicking.
+///
+/// Client methods panic when the handler is gone. Each of them has a `try_*` variant returning
+/// a `Result<_, ClientError>` instead, telling a disconnected handler apart from one that
+/// dropped the call without replying.
+///
+/// A method marked `#[timeout(ms = 50)]` waits at most that long for its reply, and returns a
+/// `Result<T, ClientError>` (or `Result<T, CallError<E>>`) reporting a `Timeout` when the
+/// handler is too slow. `client.with_timeout(duration)` gives the request/reply methods of the
+/// protocol itself with a timeout for a single call, overriding the method one. Async methods
+/// cannot have a timeout.
+///
+/// `{Trait}Client::new` creates an unbounded channel. `{Trait}Client::bounded(capacity)`
+/// creates one holding at most `capacity` messages, and applies a backpressure policy when it is
+/// full: `block` until the handler makes room, fail with an `error`, `drop_newest` or
+/// `drop_oldest`. The policy of the protocol is set with `backpressure = drop_oldest`, `block` by
+/// default, and a method marked `#[backpressure(error)]` overrides it. Client methods panic when
+/// their call is rejected, their `try_*` variant returns a `Full` error instead. The drop
+/// policies are rejected for methods with a reply, whose caller would only see its reply
+/// dropped, and so is `drop_oldest` on any call of a protocol with such methods since it can
+/// evict them.
+///
+/// The channel stays open as long as a client does. `client.downgrade()` returns a
+/// `Weak{Trait}Client` that does not keep it open, e.g. for an observer that must not prevent
+/// the receive loop of the handler from ending. Its `upgrade` gives a client back while another
+/// one is alive. `is_connected`, on both clients, tells whether the handler still has its
+/// receiver, for the senders that can tell: the `bounded` channels, flume and tokio. With the
+/// others it only turns `false` once no client is left.
+///
+/// Sync methods with a reply also have a `*_deferred` variant that sends the call and returns a
+/// `Pending<T>` handle instead of waiting, so that many calls can be in flight at once. The
+/// reply is collected with `wait`, `try_recv` or `wait_timeout`.
+///
+/// They also have a `*_async` variant awaiting the reply instead of blocking, so that callers
+/// running in an async task can share a handler thread with blocking ones. It needs no async
+/// runtime, only the `async` feature of `oneshot`, and ignores the `#[timeout]` of the method.
+///
+/// Messages are handed to a handler with `handler.dispatch(message)`, or
+/// `dispatch_with_state(message, state)`, from the `Dispatch` trait of `channel-protocol-runtime`
+/// which is implemented for every type and accepts the messages of every protocol it handles.
+///
+/// Handlers do not panic when a caller stopped waiting for a reply, they call their
+/// `reply_dropped` method with the name of the protocol method instead, which does nothing
+/// unless overridden.
+///
+/// Methods can be declared `async`. The client then awaits their reply instead of blocking, and
+/// a `Handle{Trait}Async` trait is generated whose methods and `dispatch` are all async.
+///
+/// Protocols can be composed by naming them as supertraits: `trait App: Logging + Health`. The
+/// message enum gets a variant per composed protocol nesting its message enum, the client
+/// exposes the inherited methods through the `{Trait}Sink` trait each protocol generates, and
+/// the handler traits require the composed handlers and forward their messages to them, with
+/// their own `reply_dropped`. The async handler relies on the stateless handler of the
+/// composed protocols. Composed protocols must keep the default names of their client, message
+/// and handlers, and are not composed transitively.
+///
+/// The generated code can be customized with the following options:
+/// - `client = Name`, `message = Name`, `handler = Name`, `handler_with_state = Name`,
+///   `async_handler = Name`: rename the client struct, the message enum and the handler traits.
+/// - `params = "{}Params"`: name pattern of the parameter structs, `{}` is replaced by the
+///   message variant name.
+/// - `derive(Clone, ...)`: extra derives added to the message enum and the parameter structs.
+/// - `skip(client, handler, handler_with_state, async_handler, debug)`: artifacts not to
+///   generate.
+/// - `batch`: add `batch` and `try_batch` to the client, sending the calls queued by a closure
+///   as a single message that the handler dispatches back to back:
+///   `client.batch(|b| { b.reset(); b.inc(5); b.get() })`. The methods of the builder return a
+///   `Pending<T>` handle on their reply. The message enum gets a `Batch` variant, and the state
+///   of `Handle{Trait}WithState` must be `Clone` as every message of a batch gets a copy.
+/// - `backpressure = block | error | drop_newest | drop_oldest`: policy of the `bounded`
+///   channels when they are full, and of the bounded channels of the other senders, see
+///   `channel_protocol_runtime::Backpressure::DropOldest` for those that cannot evict a queued
+///   message.
+/// - `sender = path::to::Sender`: sender type held by the client, given the message enum as its
+///   type parameter, e.g. `crossbeam_channel::Sender` or `flume::Sender` with the `crossbeam` and
+///   `flume` features of the runtime crate, `winit::event_loop::EventLoopProxy` with its `winit`
+///   feature to deliver the messages to `ApplicationHandler::user_event` directly, or any type
+///   implementing its `MessageSender` trait. The client is then created with `from_sender`,
+///   `new` and `bounded` being specific to the default sender.
+/// - `tokio`: the client sends through a `tokio::sync::mpsc` unbounded channel and all its
+///   methods are `async`, awaiting their reply. `Handle{Trait}Async` is always generated, and
+///   `Client::spawn(handler)` runs its receive loop in a tokio task, which ends once every
+///   client is dropped and returns the handler. Needs the `tokio` feature of the runtime crate.
+///   `Pending<T>` handles can be awaited. `#[timeout]`, `backpressure` and
+///   `skip(async_handler)` are not supported.
+/// - `module` or `module = name`: generate every item inside a module, named after the trait
+///   in snake case by default, so that several protocols can share method names. The items get
+///   short names: `Client`, `Message`, `Sink`, `Handle`, `HandleWithState`, `HandleAsync`
+///   and `params::{Variant}`.
 #[proc_macro_attribute]
-pub fn channel_protocol(_attr_content: TokenStream, input: TokenStream) -> TokenStream {
-    channel_protocol::build(input.into()).into()
+pub fn channel_protocol(attr: TokenStream, input: TokenStream) -> TokenStream {
+    channel_protocol::build(attr.into(), input.into()).into()
+}
+
+/// Expect an inherent impl block and generate a channel protocol from its `&mut self` methods,
+/// for actors owning their state.
+///
+/// The message enum, parameter structs and client are generated as with `#[channel_protocol]`,
+/// named after the type: `CounterMessage` and `CounterClient` for `impl Counter`. Instead of
+/// handler traits, an inherent `dispatch` method calls the matching method of the actor, and is
+/// `async` if one of the methods is or with the `tokio` option, whose `spawn` takes the actor
+/// itself. Methods marked `#[channel_actor(skip)]` are not part of the
+/// protocol, and a method marked `#[channel_actor(reply_dropped)]` taking
+/// `(&mut self, &'static str)` is called when a caller stopped waiting for a reply. Methods
+/// accept `#[timeout(ms = 50)]` and `#[backpressure(...)]` as well.
+///
+/// The options are those of `#[channel_protocol]` but the handler ones, plus `vis = pub(crate)`
+/// to set the visibility of the generated items, which is inherited by default.
+#[proc_macro_attribute]
+pub fn channel_actor(attr: TokenStream, input: TokenStream) -> TokenStream {
+    actor::build(attr.into(), input.into()).into()
+}
+
+/// Generate a client and handler traits from a hand-written message enum, like
+/// `#[channel_protocol]` does from a trait.
+///
+/// Each variant becomes a method named after it in snake case. Its fields are the arguments,
+/// unnamed ones being called `arg0`, `arg1`..., and a `oneshot::Sender<T>` field is the reply
+/// sender, making the method return `T`. In a tuple variant the reply sender must come last.
+/// Only the sender of the `oneshot` crate is accepted, written `oneshot::Sender<T>`.
+///
+/// `CounterMessage` gives `CounterClient`, `HandleCounter` and `HandleCounterWithState`, which
+/// can be renamed or skipped with `#[protocol(client = Name, handler = Name,
+/// handler_with_state = Name, skip(client, handler, handler_with_state))]` on the enum, which also
+/// accepts the `backpressure` option.
+#[proc_macro_derive(ChannelProtocol, attributes(protocol))]
+pub fn derive_channel_protocol(input: TokenStream) -> TokenStream {
+    derive::build(input.into()).into()
 }
@@ -0,0 +1,39 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::channel_protocol::{Protocol, ProtocolKind};
+
+/// Emits the protocol trait itself, with `&self` methods, so that the client and any local
+/// implementation can be used interchangeably.
+pub fn build(
+    protocol @ Protocol {
+        docs,
+        vis,
+        ident,
+        generics,
+        sub_protocols,
+        messages,
+        ..
+    }: &Protocol,
+) -> TokenStream {
+    if !matches!(protocol.kind, ProtocolKind::Trait) {
+        return TokenStream::new();
+    }
+
+    let where_clause = &generics.where_clause;
+    let allow_async_fn = protocol
+        .is_async()
+        .then(|| quote! { #[allow(async_fn_in_trait)] });
+    let messages = messages
+        .iter()
+        .map(|message| message.declaration(protocol.client_asyncness(message)));
+    let supertraits = sub_protocols.iter().map(|sub_protocol| &sub_protocol.path);
+    let colon = (!sub_protocols.is_empty()).then(|| quote! { : });
+    quote! {
+        #(#docs)*
+        #allow_async_fn
+        #vis trait #ident #generics #colon #(#supertraits)+* #where_clause {
+            #(#messages)*
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use convert_case::{Case, Casing};
-use quote::format_ident;
-use syn::{Ident, ReturnType};
+use proc_macro2::TokenStream;
+use quote::{ToTokens, format_ident, quote};
+use syn::ReturnType;
 
-use crate::channel_protocol::ProtocolMessage;
+use crate::channel_protocol::{ArgConversion, ProtocolMessage, ProtocolMessageFnArg};
 
 pub enum MessageSignatureKind {
     None,
@@ -12,12 +12,111 @@ pub enum MessageSignatureKind {
 }
 
 impl ProtocolMessage {
-    pub fn struct_ident(&self) -> Ident {
-        format_ident!("{}ParamMessage", self.pascal_case_ident())
+    /// Arguments as accepted by the client, `impl Into<T>` and `impl AsRef<T>` included.
+    pub fn client_args(&self) -> TokenStream {
+        let args = self.args.iter().map(|arg| {
+            let ProtocolMessageFnArg {
+                ident, client_ty, ..
+            } = arg;
+            quote! { #ident: #client_ty }
+        });
+        quote! { #(#args),* }
     }
 
-    pub fn pascal_case_ident(&self) -> Ident {
-        format_ident!("{}", self.ident.to_string().to_case(Case::Pascal))
+    /// `T` and `E` of a method returning `Result<T, E>`.
+    pub fn result_types(&self) -> Option<(&syn::Type, &syn::Type)> {
+        let ReturnType::Type(_, ty) = &self.output else {
+            return None;
+        };
+        let syn::Type::Path(syn::TypePath { qself: None, path }) = &**ty else {
+            return None;
+        };
+        let segment = path.segments.last()?;
+        if segment.ident != "Result" {
+            return None;
+        }
+        let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+            return None;
+        };
+        match arguments.args.iter().collect::<Vec<_>>()[..] {
+            [
+                syn::GenericArgument::Type(ok),
+                syn::GenericArgument::Type(err),
+            ] => Some((ok, err)),
+            _ => None,
+        }
+    }
+
+    /// Return type of the client method. Methods returning `Result<T, E>` return
+    /// `Result<T, CallError<E>>` so that call failures are reported along with handler errors.
+    /// Methods with a timeout return `Result<T, ClientError>` since the call can time out.
+    pub fn client_output(&self) -> TokenStream {
+        if self.timeout.is_some() {
+            return self.timed_output();
+        }
+        self.untimed_output()
+    }
+
+    /// Return type of the client method when the call has no timeout.
+    pub fn untimed_output(&self) -> TokenStream {
+        match self.result_types() {
+            Some((ok, err)) => quote! {
+                -> std::result::Result<#ok, ::channel_protocol_runtime::CallError<#err>>
+            },
+            None => self.output.to_token_stream(),
+        }
+    }
+
+    /// Return type of the client method when the call has a timeout.
+    pub fn timed_output(&self) -> TokenStream {
+        match (self.result_types(), &self.output) {
+            (Some((ok, err)), _) => quote! {
+                -> std::result::Result<#ok, ::channel_protocol_runtime::CallError<#err>>
+            },
+            (None, ReturnType::Type(_, ty)) => quote! {
+                -> std::result::Result<#ty, ::channel_protocol_runtime::ClientError>
+            },
+            (None, ReturnType::Default) => quote! {},
+        }
+    }
+
+    /// `Duration` of the `#[timeout(ms = ...)]` attribute.
+    pub fn timeout_duration(&self) -> Option<TokenStream> {
+        self.timeout
+            .as_ref()
+            .map(|ms| quote! { std::time::Duration::from_millis(#ms) })
+    }
+
+    /// The `#[cfg]` subset of [`Self::attrs`], for generated places that only accept `cfg`
+    /// such as match arms.
+    pub fn cfg_attrs(&self) -> impl Iterator<Item = &syn::Attribute> {
+        self.attrs.iter().filter(|attr| attr.path().is_ident("cfg"))
+    }
+
+    /// Name of the `try_*` variant of the client method.
+    pub fn try_ident(&self) -> syn::Ident {
+        format_ident!("try_{}", self.ident)
+    }
+
+    /// Name of the `*_deferred` variant of the client method.
+    pub fn deferred_ident(&self) -> syn::Ident {
+        format_ident!("{}_deferred", self.ident)
+    }
+
+    /// Name of the `*_async` variant of the client method.
+    pub fn async_ident(&self) -> syn::Ident {
+        format_ident!("{}_async", self.ident)
+    }
+
+    /// Whether the client has a `*_deferred` variant of the method, only sync methods with a
+    /// reply have one.
+    pub fn has_deferred(&self) -> bool {
+        self.asyncness.is_none() && self.has_reply()
+    }
+
+    /// Whether the message carries a reply sender.
+    pub fn has_reply(&self) -> bool {
+        matches!(self.output, ReturnType::Type(_, _))
     }
 
     pub fn signature_kind(&self) -> MessageSignatureKind {
@@ -32,3 +131,15 @@ impl ProtocolMessage {
         }
     }
 }
+
+impl ProtocolMessageFnArg {
+    /// Field initializer of the parameter struct, converting the client argument if needed.
+    pub fn field_init(&self) -> TokenStream {
+        let ident = &self.ident;
+        match self.conversion {
+            None => quote! { #ident },
+            Some(ArgConversion::Into) => quote! { #ident: #ident.into() },
+            Some(ArgConversion::AsRef) => quote! { #ident: #ident.as_ref().to_owned() },
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod message;
 pub mod protocol;
+pub mod sub_protocol;
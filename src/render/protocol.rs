@@ -1,10 +1,325 @@
 use convert_case::{Case, Casing};
-use quote::format_ident;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::visit::Visit;
 
-use crate::channel_protocol::Protocol;
+use crate::channel_protocol::{MessageFields, Protocol, ProtocolMessage};
 
 impl Protocol {
+    /// Name of the module holding the generated items, if any.
+    pub fn module_ident(&self) -> Option<syn::Ident> {
+        self.config.module.as_ref().map(|module| {
+            module
+                .clone()
+                .unwrap_or_else(|| format_ident!("{}", self.ident.to_string().to_case(Case::Snake)))
+        })
+    }
+
+    /// Visibility of the generated items. Inside a module they are all public and the
+    /// module itself carries the trait visibility.
+    pub fn item_vis(&self) -> syn::Visibility {
+        if self.config.module.is_some() {
+            syn::parse_quote!(pub)
+        } else {
+            self.vis.clone()
+        }
+    }
+
     pub fn message_enum_ident(&self) -> syn::Ident {
-        format_ident!("{}Message", self.ident.to_string().to_case(Case::Pascal))
+        self.config.message.clone().unwrap_or_else(|| {
+            if self.config.module.is_some() {
+                format_ident!("Message")
+            } else {
+                format_ident!("{}Message", self.ident.to_string().to_case(Case::Pascal))
+            }
+        })
+    }
+
+    pub fn client_ident(&self) -> syn::Ident {
+        self.config.client.clone().unwrap_or_else(|| {
+            if self.config.module.is_some() {
+                format_ident!("Client")
+            } else {
+                format_ident!("{}Client", self.ident)
+            }
+        })
+    }
+
+    pub fn handler_ident(&self) -> syn::Ident {
+        self.config.handler.clone().unwrap_or_else(|| {
+            if self.config.module.is_some() {
+                format_ident!("Handle")
+            } else {
+                format_ident!("Handle{}", self.ident)
+            }
+        })
+    }
+
+    pub fn handler_with_state_ident(&self) -> syn::Ident {
+        self.config.handler_with_state.clone().unwrap_or_else(|| {
+            if self.config.module.is_some() {
+                format_ident!("HandleWithState")
+            } else {
+                format_ident!("Handle{}WithState", self.ident)
+            }
+        })
+    }
+
+    pub fn async_handler_ident(&self) -> syn::Ident {
+        self.config.async_handler.clone().unwrap_or_else(|| {
+            if self.config.module.is_some() {
+                format_ident!("HandleAsync")
+            } else {
+                format_ident!("Handle{}Async", self.ident)
+            }
+        })
+    }
+
+    /// Trait implemented by anything that can send the protocol messages, the protocol trait is
+    /// implemented for all of them. Clients of composed protocols implement it to expose the
+    /// inherited methods.
+    pub fn sink_ident(&self) -> syn::Ident {
+        if self.config.module.is_some() {
+            format_ident!("Sink")
+        } else {
+            format_ident!("{}Sink", self.ident)
+        }
+    }
+
+    /// Whether the protocol declares `async` methods or has a `tokio` client, the async handler
+    /// is then generated.
+    pub fn is_async(&self) -> bool {
+        self.config.tokio
+            || self
+                .messages
+                .iter()
+                .any(|message| message.asyncness.is_some())
+    }
+
+    /// Whether the client method of `message` is `async`, as every method of a `tokio` client.
+    pub fn client_asyncness(&self, message: &ProtocolMessage) -> Option<syn::Token![async]> {
+        message
+            .asyncness
+            .or_else(|| self.config.tokio.then(Default::default))
+    }
+
+    /// Whether the client has an `*_async` variant of the method, awaiting the reply of a sync
+    /// method with one.
+    pub fn has_async_variant(&self, message: &ProtocolMessage) -> bool {
+        self.client_asyncness(message).is_none() && message.has_reply()
+    }
+
+    pub fn message_struct_ident(&self, message: &ProtocolMessage) -> syn::Ident {
+        let variant = message.variant.clone();
+        match &self.config.params {
+            Some(pattern) => {
+                format_ident!("{}", pattern.value().replace("{}", &variant.to_string()))
+            }
+            None if self.config.module.is_some() => variant,
+            None => format_ident!("{}ParamMessage", variant),
+        }
+    }
+
+    /// Type of the message enum, with the protocol type parameters.
+    pub fn message_enum_type(&self) -> TokenStream {
+        let ident = self.message_enum_ident();
+        let (_, ty_generics, _) = self.generics.split_for_impl();
+        quote! { #ident #ty_generics }
+    }
+
+    /// Type of the parameter struct of a message, with the protocol type parameters.
+    pub fn message_struct_type(&self, message: &ProtocolMessage) -> TokenStream {
+        let path = self.message_struct_path(message);
+        let (_, ty_generics, _) = self.generics.split_for_impl();
+        quote! { #path #ty_generics }
+    }
+
+    /// Name of the state type parameter of the stateful handler, chosen to not clash with
+    /// the protocol type parameters.
+    pub fn state_type_ident(&self) -> syn::Ident {
+        if self.generics.type_params().any(|param| param.ident == "S") {
+            format_ident!("State")
+        } else {
+            format_ident!("S")
+        }
+    }
+
+    /// Name of the handler type parameter of the dispatch implementations of the message enum,
+    /// chosen to not clash with the protocol type parameters.
+    pub fn handler_type_ident(&self) -> syn::Ident {
+        if self.generics.type_params().any(|param| param.ident == "H") {
+            format_ident!("Handler")
+        } else {
+            format_ident!("H")
+        }
+    }
+
+    /// State passed to the stateful handler methods by the dispatch, a copy of it when batches
+    /// dispatch several messages with the same state.
+    pub fn state_arg(&self) -> TokenStream {
+        if self.config.batch {
+            quote! { state.clone() }
+        } else {
+            quote! { state }
+        }
+    }
+
+    /// `Backpressure` variant applied to `message`, the policy of its `#[backpressure(...)]`
+    /// attribute or else the one of the protocol.
+    pub fn effective_backpressure<'a>(
+        &'a self,
+        message: Option<&'a ProtocolMessage>,
+    ) -> Option<&'a syn::Ident> {
+        message
+            .and_then(|message| message.backpressure.as_ref())
+            .or(self.config.backpressure.as_ref())
+    }
+
+    /// `Option<Backpressure>` given to the sender for `message`, its effective policy.
+    pub fn backpressure_arg(&self, message: Option<&ProtocolMessage>) -> TokenStream {
+        match self.effective_backpressure(message) {
+            Some(policy) => quote! { Some(::channel_protocol_runtime::Backpressure::#policy) },
+            None => quote! { None },
+        }
+    }
+
+    /// Type of the sender held by the client, `ClientSender` or tokio's `UnboundedSender` for a
+    /// `tokio` client, unless the protocol names another one with `sender = path`.
+    pub fn sender_type(&self) -> TokenStream {
+        let message_enum_type = self.message_enum_type();
+        match &self.config.sender {
+            Some(sender) => quote! { #sender<#message_enum_type> },
+            None if self.config.tokio => quote! {
+                ::channel_protocol_runtime::tokio::sync::mpsc::UnboundedSender<#message_enum_type>
+            },
+            None => quote! { ::channel_protocol_runtime::ClientSender<#message_enum_type> },
+        }
+    }
+
+    /// Name of the type parameter of the blanket protocol trait implementation over sinks.
+    pub fn sink_type_ident(&self) -> syn::Ident {
+        if self.generics.type_params().any(|param| param.ident == "C") {
+            format_ident!("Sender")
+        } else {
+            format_ident!("C")
+        }
+    }
+
+    /// Protocol type parameters mentioned by `ty`.
+    pub fn mentioned_type_params(&self, ty: &syn::Type) -> Vec<&syn::Ident> {
+        struct Visitor<'a> {
+            params: Vec<&'a syn::Ident>,
+            mentioned: Vec<&'a syn::Ident>,
+        }
+
+        impl<'ast> Visit<'ast> for Visitor<'_> {
+            fn visit_path(&mut self, path: &'ast syn::Path) {
+                if path.leading_colon.is_none()
+                    && let Some(first) = path.segments.first()
+                    && let Some(param) = self.params.iter().find(|param| **param == &first.ident)
+                    && !self.mentioned.contains(param)
+                {
+                    self.mentioned.push(param);
+                }
+                syn::visit::visit_path(self, path);
+            }
+        }
+
+        let mut visitor = Visitor {
+            params: self
+                .generics
+                .type_params()
+                .map(|param| &param.ident)
+                .collect(),
+            mentioned: Vec::new(),
+        };
+        visitor.visit_type(ty);
+        visitor.mentioned
+    }
+
+    /// Protocol type parameters that do not appear in the arguments of a message, typically
+    /// because they only appear in its return type. The parameter struct marks them with a
+    /// `PhantomData` field.
+    pub fn phantom_type_params(&self, message: &ProtocolMessage) -> Vec<&syn::Ident> {
+        let mentioned = message
+            .args
+            .iter()
+            .flat_map(|arg| self.mentioned_type_params(&arg.ty))
+            .collect::<Vec<_>>();
+        self.generics
+            .type_params()
+            .map(|param| &param.ident)
+            .filter(|param| !mentioned.contains(param))
+            .collect()
+    }
+
+    /// Path to the parameter struct of a message, from the generated items.
+    pub fn message_struct_path(&self, message: &ProtocolMessage) -> TokenStream {
+        let ident = self.message_struct_ident(message);
+        if self.config.module.is_some() {
+            quote! { params::#ident }
+        } else {
+            quote! { #ident }
+        }
+    }
+
+    /// Expression building a message from the client arguments, and from `tx` if the message
+    /// has a reply.
+    pub fn message_value(&self, message: &ProtocolMessage) -> TokenStream {
+        let enum_ident = self.message_enum_ident();
+        let variant = &message.variant;
+        let tx = message.has_reply().then(|| quote! { tx });
+        let fields = message.args.iter().map(|arg| arg.field_init());
+        match &message.fields {
+            MessageFields::Params if message.args.is_empty() => match tx {
+                Some(tx) => quote! { #enum_ident::#variant(#tx) },
+                None => quote! { #enum_ident::#variant },
+            },
+            MessageFields::Params => {
+                let struct_path = self.message_struct_path(message);
+                let marker = (!self.phantom_type_params(message).is_empty())
+                    .then(|| quote! { _marker: std::marker::PhantomData, });
+                let tx = tx.map(|tx| quote! { , #tx });
+                quote! {
+                    #enum_ident::#variant(#struct_path {
+                        #(#fields,)*
+                        #marker
+                    } #tx)
+                }
+            }
+            MessageFields::Unit => quote! { #enum_ident::#variant },
+            MessageFields::Named { reply } => {
+                let reply = reply.as_ref().map(|reply| quote! { #reply: tx });
+                quote! { #enum_ident::#variant { #(#fields,)* #reply } }
+            }
+            MessageFields::Unnamed => quote! { #enum_ident::#variant(#(#fields,)* #tx) },
+        }
+    }
+
+    /// Pattern destructuring a message into its arguments, and into `tx` if the message has a
+    /// reply.
+    pub fn message_pattern(&self, message: &ProtocolMessage) -> TokenStream {
+        let enum_ident = self.message_enum_ident();
+        let variant = &message.variant;
+        let tx = message.has_reply().then(|| quote! { tx });
+        let args = message.args.iter().map(|arg| &arg.ident);
+        match &message.fields {
+            MessageFields::Params if message.args.is_empty() => match tx {
+                Some(tx) => quote! { #enum_ident::#variant(#tx) },
+                None => quote! { #enum_ident::#variant },
+            },
+            MessageFields::Params => {
+                let struct_path = self.message_struct_path(message);
+                let rest = (!self.phantom_type_params(message).is_empty()).then(|| quote! { , .. });
+                let tx = tx.map(|tx| quote! { , #tx });
+                quote! { #enum_ident::#variant(#struct_path { #(#args),* #rest } #tx) }
+            }
+            MessageFields::Unit => quote! { #enum_ident::#variant },
+            MessageFields::Named { reply } => {
+                let reply = reply.as_ref().map(|reply| quote! { #reply: tx });
+                quote! { #enum_ident::#variant { #(#args,)* #reply } }
+            }
+            MessageFields::Unnamed => quote! { #enum_ident::#variant(#(#args,)* #tx) },
+        }
     }
 }
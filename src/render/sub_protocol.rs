@@ -0,0 +1,52 @@
+use quote::format_ident;
+
+use crate::channel_protocol::SubProtocol;
+
+impl SubProtocol {
+    /// Name of the trait, also used as the variant nesting its messages.
+    pub fn variant_ident(&self) -> syn::Ident {
+        self.path
+            .segments
+            .last()
+            .expect("a supertrait path has a segment")
+            .ident
+            .clone()
+    }
+
+    /// Path to an item generated for the composed protocol, with its type arguments.
+    fn item_path(&self, ident: syn::Ident) -> syn::Path {
+        let mut path = self.path.clone();
+        if let Some(segment) = path.segments.last_mut() {
+            segment.ident = ident;
+        }
+        path
+    }
+
+    pub fn message_enum_path(&self) -> syn::Path {
+        self.item_path(format_ident!("{}Message", self.variant_ident()))
+    }
+
+    pub fn sink_path(&self) -> syn::Path {
+        self.item_path(format_ident!("{}Sink", self.variant_ident()))
+    }
+
+    pub fn handler_path(&self) -> syn::Path {
+        self.item_path(format_ident!("Handle{}", self.variant_ident()))
+    }
+
+    /// Path to the stateful handler, with `state` appended to the type arguments.
+    pub fn handler_with_state_path(&self, state: &syn::Ident) -> syn::Path {
+        let mut path = self.item_path(format_ident!("Handle{}WithState", self.variant_ident()));
+        if let Some(segment) = path.segments.last_mut() {
+            match &mut segment.arguments {
+                syn::PathArguments::AngleBracketed(arguments) => {
+                    arguments.args.push(syn::parse_quote!(#state));
+                }
+                arguments => {
+                    *arguments = syn::PathArguments::AngleBracketed(syn::parse_quote!(<#state>))
+                }
+            }
+        }
+        path
+    }
+}
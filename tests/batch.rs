@@ -0,0 +1,152 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    thread::{self, JoinHandle},
+};
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::{ClientError, Dispatch};
+
+#[channel_protocol(batch)]
+trait Counter {
+    fn reset();
+    fn inc(by: i32);
+    fn get() -> i32;
+}
+
+#[derive(Default)]
+struct State {
+    counter: i32,
+}
+
+impl HandleCounter for State {
+    fn reset(&mut self) {
+        self.counter = 0;
+    }
+
+    fn inc(&mut self, by: i32) {
+        self.counter += by;
+    }
+
+    fn get(&mut self) -> i32 {
+        self.counter
+    }
+}
+
+/// Counts its copies.
+struct Copies(Rc<Cell<usize>>);
+
+impl Clone for Copies {
+    fn clone(&self) -> Self {
+        self.0.set(self.0.get() + 1);
+        Self(self.0.clone())
+    }
+}
+
+impl HandleCounterWithState<Copies> for State {
+    fn reset(&mut self, _copies: Copies) {
+        self.counter = 0;
+    }
+
+    fn inc(&mut self, by: i32, _copies: Copies) {
+        self.counter += by;
+    }
+
+    fn get(&mut self, copies: Copies) -> i32 {
+        copies.0.get() as i32
+    }
+}
+
+fn spawn_counter() -> (CounterClient, JoinHandle<State>) {
+    let (client, rx) = CounterClient::new();
+    let handle = thread::spawn(move || {
+        let mut state = State::default();
+        for message in rx {
+            state.dispatch(message);
+        }
+        state
+    });
+    (client, handle)
+}
+
+#[test]
+fn batch_sends_its_calls_as_one_message() {
+    let (client, rx) = CounterClient::new();
+    client.batch(|b| {
+        b.reset();
+        b.inc(1);
+    });
+    let messages = rx.try_iter().collect::<Vec<_>>();
+    assert!(matches!(
+        &messages[..],
+        [CounterMessage::Batch(calls)] if calls.len() == 2
+    ));
+}
+
+#[test]
+fn batch_runs_its_calls_in_order() {
+    let (client, handle) = spawn_counter();
+    client.inc(10);
+    let (before, after) = client.batch(|b| {
+        let before = b.get();
+        b.reset();
+        b.inc(5);
+        (before, b.get())
+    });
+    assert_eq!(Ok(10), before.wait());
+    assert_eq!(Ok(5), after.wait());
+    drop(client);
+    assert_eq!(5, handle.join().unwrap().counter);
+}
+
+#[test]
+fn batch_runs_without_calls_of_other_clients_in_between() {
+    let (client, handle) = spawn_counter();
+    let callers = (0..8)
+        .map(|caller| {
+            let client = client.clone();
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    let reply = client.batch(|b| {
+                        b.reset();
+                        b.inc(caller);
+                        b.inc(caller);
+                        b.get()
+                    });
+                    assert_eq!(Ok(2 * caller), reply.wait());
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for caller in callers {
+        caller.join().unwrap();
+    }
+    drop(client);
+    handle.join().unwrap();
+}
+
+#[test]
+fn batch_hands_a_copy_of_the_state_to_every_call() {
+    let (client, rx) = CounterClient::new();
+    let copies = client.batch(|b| {
+        b.reset();
+        b.inc(1);
+        b.get()
+    });
+    let mut state = State::default();
+    for message in rx.try_iter() {
+        state.dispatch_with_state(message, Copies(Rc::new(Cell::new(0))));
+    }
+    assert_eq!(1, state.counter);
+    assert_eq!(Ok(3), copies.wait());
+}
+
+#[test]
+fn batch_to_a_gone_handler_fails() {
+    let (client, rx) = CounterClient::new();
+    drop(rx);
+    assert_eq!(
+        Err(ClientError::Disconnected),
+        client.try_batch(|b| b.inc(1))
+    );
+}
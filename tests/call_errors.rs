@@ -0,0 +1,45 @@
+use std::thread;
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::{CallError, ClientError, Dispatch};
+
+#[channel_protocol]
+trait Parser {
+    /// Panics on an empty `text`.
+    fn parse(text: String) -> Result<u32, String>;
+}
+
+struct State;
+
+impl HandleParser for State {
+    fn parse(&mut self, text: String) -> Result<u32, String> {
+        assert!(!text.is_empty(), "nothing to parse");
+        text.parse().map_err(|_| text)
+    }
+}
+
+#[test]
+fn call_errors_report_how_the_call_failed() {
+    let (client, rx) = ParserClient::new();
+    let handle = thread::spawn(move || {
+        for message in rx {
+            State.dispatch(message);
+        }
+    });
+
+    assert_eq!(Ok(3), client.parse("3".to_owned()));
+    assert_eq!(
+        Err(CallError::Failed("three".to_owned())),
+        client.parse("three".to_owned())
+    );
+
+    // The handler thread drops the reply sender of the call it panics on, and its receiver.
+    assert_eq!(Err(CallError::Panicked), client.parse(String::new()));
+    assert!(handle.join().is_err());
+
+    assert_eq!(Err(CallError::Disconnected), client.parse("3".to_owned()));
+    assert_eq!(
+        Err(ClientError::Disconnected),
+        client.try_parse("3".to_owned())
+    );
+}
@@ -0,0 +1,22 @@
+use channel_protocol::channel_actor;
+
+struct Counter(i32);
+
+#[channel_actor]
+impl Counter {
+    #[timeout(ms = 10)]
+    fn get(&self) -> i32 {
+        self.0
+    }
+
+    #[backpressure(error)]
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+}
+
+fn main() {
+    let mut counter = Counter(1);
+    counter.reset();
+    assert_eq!(0, counter.get());
+}
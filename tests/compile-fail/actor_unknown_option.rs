@@ -0,0 +1,25 @@
+use channel_protocol::channel_actor;
+
+struct Counter(i32);
+
+#[channel_actor(bogus)]
+impl Counter {
+    fn new() -> Self {
+        Self(0)
+    }
+
+    #[timeout(ms = 10)]
+    fn get(&mut self) -> i32 {
+        self.0
+    }
+
+    #[channel_actor(skip)]
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+}
+
+fn main() {
+    let mut counter = Counter::new();
+    counter.reset();
+}
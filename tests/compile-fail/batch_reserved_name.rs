@@ -0,0 +1,10 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol(batch)]
+trait Protocol {
+    fn batch(x: i32);
+    fn try_batch();
+    fn get() -> i32;
+}
+
+fn main() {}
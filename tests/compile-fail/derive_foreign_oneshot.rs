@@ -0,0 +1,9 @@
+use channel_protocol::ChannelProtocol;
+
+#[derive(ChannelProtocol)]
+enum CounterMessage {
+    Inc(i32),
+    Get(tokio::sync::oneshot::Sender<i32>),
+}
+
+fn main() {}
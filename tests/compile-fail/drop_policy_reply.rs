@@ -0,0 +1,20 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol]
+trait Lossy {
+    #[backpressure(drop_newest)]
+    fn load() -> u32;
+    #[backpressure(block)]
+    fn peek() -> u32;
+    #[backpressure(drop_oldest)]
+    fn log(line: String);
+}
+
+#[channel_protocol(backpressure = drop_newest, batch)]
+trait Batched {
+    #[backpressure(error)]
+    fn get() -> u32;
+    fn set(value: u32);
+}
+
+fn main() {}
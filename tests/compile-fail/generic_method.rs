@@ -0,0 +1,8 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol]
+trait Protocol {
+    fn set<T>(value: T);
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol]
+trait Protocol {
+    fn set((x, y): (i32, i32));
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol]
+trait Protocol {
+    fn consume(self);
+    fn boxed(self: Box<Self>);
+}
+
+fn main() {}
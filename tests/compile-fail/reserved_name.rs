@@ -0,0 +1,10 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol]
+trait Protocol {
+    fn new();
+    fn dispatch();
+    fn send_message();
+}
+
+fn main() {}
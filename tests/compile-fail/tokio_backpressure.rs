@@ -0,0 +1,14 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol(tokio, backpressure = error)]
+trait Protocol {
+    fn put(value: i32);
+}
+
+#[channel_protocol(tokio)]
+trait Other {
+    #[backpressure(drop_newest)]
+    fn put(value: i32);
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol(tokio, skip(async_handler))]
+trait Protocol {
+    fn put(value: i32);
+}
+
+#[channel_protocol(skip(async_handler), tokio)]
+trait Other {
+    fn put(value: i32);
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol(tokio)]
+trait Protocol {
+    fn spawn(x: i32);
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol(tokio)]
+trait Protocol {
+    #[timeout(ms = 5)]
+    fn get() -> i32;
+}
+
+fn main() {}
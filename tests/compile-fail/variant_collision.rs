@@ -0,0 +1,9 @@
+use channel_protocol::channel_protocol;
+
+#[channel_protocol]
+trait Protocol {
+    fn get_x() -> i32;
+    fn getX() -> i32;
+}
+
+fn main() {}
@@ -0,0 +1,119 @@
+use std::sync::mpsc::Receiver;
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::Dispatch;
+
+#[channel_protocol]
+trait Health {
+    fn status() -> String;
+}
+
+#[channel_protocol]
+trait Store {
+    fn put(value: u32);
+    fn values() -> Vec<u32>;
+}
+
+#[channel_protocol]
+trait Service: Health + Store {
+    fn clear();
+}
+
+#[derive(Default)]
+struct State {
+    values: Vec<u32>,
+    dropped: Vec<&'static str>,
+}
+
+impl HandleHealth for State {
+    fn status(&mut self) -> String {
+        format!("{} values", self.values.len())
+    }
+
+    fn reply_dropped(&mut self, method: &'static str) {
+        self.dropped.push(method);
+    }
+}
+
+impl HandleStore for State {
+    fn put(&mut self, value: u32) {
+        self.values.push(value);
+    }
+
+    fn values(&mut self) -> Vec<u32> {
+        self.values.clone()
+    }
+}
+
+impl HandleService for State {
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    fn reply_dropped(&mut self, method: &'static str) {
+        self.dropped.push(method);
+        self.dropped.push("service");
+    }
+}
+
+fn serve<H: HandleService>(handler: &mut H, rx: Receiver<ServiceMessage>) {
+    for message in rx {
+        handler.dispatch(message);
+    }
+}
+
+#[test]
+fn composed_handler_dispatches_every_message() {
+    let (client, rx) = ServiceClient::new();
+    let handle = std::thread::spawn(move || {
+        let mut state = State::default();
+        for message in rx {
+            state.dispatch(message);
+        }
+        state
+    });
+
+    client.put(1);
+    client.put(2);
+    assert_eq!("2 values", client.status());
+    client.clear();
+    client.put(3);
+    assert_eq!(vec![3], client.values());
+    drop(client);
+    assert_eq!(vec![3], handle.join().unwrap().values);
+}
+
+#[test]
+fn generic_composed_handler_dispatches_every_message() {
+    let (client, rx) = ServiceClient::new();
+    client.put(4);
+    drop(client);
+    let mut state = State::default();
+    serve(&mut state, rx);
+    assert_eq!(vec![4], state.values);
+}
+
+#[test]
+fn composed_handler_dispatches_the_messages_of_its_supertraits() {
+    let mut state = State::default();
+    state.dispatch(StoreMessage::Put(PutParamMessage { value: 6 }));
+    let (tx, rx) = oneshot::channel();
+    state.dispatch(HealthMessage::Status(tx));
+    assert_eq!("1 values", rx.recv().unwrap());
+}
+
+#[test]
+fn composed_handler_reports_dropped_replies_of_its_supertraits() {
+    let mut state = State::default();
+
+    let (tx, rx) = oneshot::channel();
+    drop(rx);
+    state.dispatch(ServiceMessage::Health(HealthMessage::Status(tx)));
+    assert_eq!(vec!["status", "service"], state.dropped);
+
+    state.dropped.clear();
+    let (tx, rx) = oneshot::channel();
+    drop(rx);
+    state.dispatch(HealthMessage::Status(tx));
+    assert_eq!(vec!["status"], state.dropped);
+}
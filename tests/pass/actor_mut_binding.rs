@@ -0,0 +1,20 @@
+use channel_protocol::channel_actor;
+
+#[derive(Default)]
+struct Stack(Vec<i32>);
+
+// `mut` bindings of the arguments stay in the method bodies.
+#[channel_actor]
+impl Stack {
+    fn push(&mut self, mut values: Vec<i32>) {
+        self.0.append(&mut values);
+    }
+}
+
+fn main() {
+    let (client, rx) = StackClient::new();
+    client.push(vec![1, 2]);
+    let mut stack = Stack::default();
+    stack.dispatch(rx.recv().unwrap());
+    assert_eq!(vec![1, 2], stack.0);
+}
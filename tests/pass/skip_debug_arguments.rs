@@ -0,0 +1,11 @@
+use channel_protocol::channel_protocol;
+
+// Without the `Debug` of the message enum, arguments do not need to implement it.
+struct Opaque;
+
+#[channel_protocol(skip(debug))]
+trait Protocol {
+    fn put(value: Opaque);
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use channel_protocol::channel_protocol;
+
+// Tokio clients have no `*_async` variants, `get_async` is a method of its own.
+#[channel_protocol(tokio)]
+trait Protocol {
+    fn get() -> i32;
+    fn get_async() -> i32;
+}
+
+fn main() {}
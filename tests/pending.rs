@@ -0,0 +1,75 @@
+use std::{thread, time::Duration};
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::{ClientError, Dispatch};
+
+#[channel_protocol]
+trait Store {
+    fn put(value: u32);
+    fn get(index: usize) -> Option<u32>;
+}
+
+#[derive(Default)]
+struct State {
+    values: Vec<u32>,
+}
+
+impl HandleStore for State {
+    fn put(&mut self, value: u32) {
+        self.values.push(value);
+    }
+
+    fn get(&mut self, index: usize) -> Option<u32> {
+        self.values.get(index).copied()
+    }
+}
+
+#[test]
+fn deferred_calls_collect_their_reply_later() {
+    let (client, rx) = StoreClient::new();
+    let mut state = State::default();
+    client.put(1);
+    client.put(2);
+    let first = client.get_deferred(0);
+    let second = client.get_deferred(1);
+    assert_eq!(Ok(None), first.try_recv());
+    assert_eq!(
+        Err(ClientError::Timeout),
+        second.wait_timeout(Duration::from_millis(10))
+    );
+
+    for message in rx.try_iter() {
+        state.dispatch(message);
+    }
+    assert_eq!(Ok(Some(Some(2))), second.try_recv());
+    assert_eq!(Err(ClientError::ReplyDropped), second.try_recv());
+    assert_eq!(Ok(Some(1)), first.wait());
+}
+
+#[test]
+fn deferred_calls_report_a_gone_handler() {
+    let (client, rx) = StoreClient::new();
+    let pending = client.get_deferred(0);
+    drop(rx);
+    assert_eq!(Err(ClientError::ReplyDropped), pending.wait());
+    assert_eq!(
+        Err(ClientError::Disconnected),
+        client.get_deferred(0).wait()
+    );
+}
+
+#[tokio::test]
+async fn pending_handles_can_be_awaited() {
+    let (client, rx) = StoreClient::new();
+    let handle = thread::spawn(move || {
+        let mut state = State::default();
+        for message in rx {
+            state.dispatch(message);
+        }
+    });
+
+    client.put(7);
+    assert_eq!(Ok(Some(7)), client.get_deferred(0).await);
+    drop(client);
+    handle.join().unwrap();
+}
@@ -0,0 +1,116 @@
+use channel_protocol::{channel_actor, channel_protocol};
+use channel_protocol_runtime::Dispatch;
+
+#[channel_protocol]
+trait Store {
+    fn put(value: u32);
+    fn get() -> u32;
+}
+
+#[derive(Default)]
+struct State {
+    value: u32,
+    dropped: Vec<&'static str>,
+}
+
+impl HandleStore for State {
+    fn put(&mut self, value: u32) {
+        self.value = value;
+    }
+
+    fn get(&mut self) -> u32 {
+        self.value
+    }
+
+    fn reply_dropped(&mut self, method: &'static str) {
+        self.dropped.push(method);
+    }
+}
+
+impl HandleStoreWithState<u32> for State {
+    fn put(&mut self, value: u32, offset: u32) {
+        self.value = value + offset;
+    }
+
+    fn get(&mut self, offset: u32) -> u32 {
+        self.value + offset
+    }
+
+    fn reply_dropped(&mut self, method: &'static str) {
+        self.dropped.push(method);
+    }
+}
+
+/// Keeps the default `reply_dropped`.
+struct Silent;
+
+impl HandleStore for Silent {
+    fn put(&mut self, _value: u32) {}
+
+    fn get(&mut self) -> u32 {
+        0
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    count: u32,
+    dropped: Vec<&'static str>,
+}
+
+#[channel_actor]
+impl Counter {
+    fn count(&mut self) -> u32 {
+        self.count
+    }
+
+    #[channel_actor(reply_dropped)]
+    fn lost(&mut self, method: &'static str) {
+        self.dropped.push(method);
+    }
+}
+
+#[test]
+fn handlers_report_the_calls_whose_caller_stopped_waiting() {
+    let (client, rx) = StoreClient::new();
+    let mut state = State::default();
+    client.put(1);
+    drop(client.get_deferred());
+    let kept = client.get_deferred();
+    for message in rx.try_iter() {
+        state.dispatch(message);
+    }
+    assert_eq!(Ok(1), kept.wait());
+    assert_eq!(vec!["get"], state.dropped);
+}
+
+#[test]
+fn stateful_handlers_report_the_calls_whose_caller_stopped_waiting() {
+    let (client, rx) = StoreClient::new();
+    let mut state = State::default();
+    drop(client.get_deferred());
+    for message in rx.try_iter() {
+        state.dispatch_with_state(message, 1);
+    }
+    assert_eq!(vec!["get"], state.dropped);
+}
+
+#[test]
+fn handlers_ignore_dropped_replies_by_default() {
+    let (client, rx) = StoreClient::new();
+    drop(client.get_deferred());
+    for message in rx.try_iter() {
+        Silent.dispatch(message);
+    }
+}
+
+#[test]
+fn actors_report_the_calls_whose_caller_stopped_waiting() {
+    let (client, rx) = CounterClient::new();
+    let mut counter = Counter::default();
+    drop(client.count_deferred());
+    for message in rx.try_iter() {
+        counter.dispatch(message);
+    }
+    assert_eq!(vec!["count"], counter.dropped);
+}
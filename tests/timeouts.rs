@@ -0,0 +1,101 @@
+use std::{
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::{CallError, ClientError, Dispatch};
+
+#[channel_protocol]
+trait Worker {
+    #[timeout(ms = 20)]
+    fn slow(ms: u64) -> u64;
+    fn sleep(ms: u64) -> u64;
+    #[timeout(ms = 20)]
+    fn parse(text: String, ms: u64) -> Result<u32, String>;
+}
+
+#[derive(Default)]
+struct State {
+    dropped: Vec<&'static str>,
+}
+
+impl HandleWorker for State {
+    fn slow(&mut self, ms: u64) -> u64 {
+        self.sleep(ms)
+    }
+
+    fn sleep(&mut self, ms: u64) -> u64 {
+        thread::sleep(Duration::from_millis(ms));
+        ms
+    }
+
+    fn parse(&mut self, text: String, ms: u64) -> Result<u32, String> {
+        self.sleep(ms);
+        text.parse().map_err(|_| text)
+    }
+
+    fn reply_dropped(&mut self, method: &'static str) {
+        self.dropped.push(method);
+    }
+}
+
+fn spawn_worker() -> (WorkerClient, JoinHandle<State>) {
+    let (client, rx) = WorkerClient::new();
+    let handle = thread::spawn(move || {
+        let mut state = State::default();
+        for message in rx {
+            state.dispatch(message);
+        }
+        state
+    });
+    (client, handle)
+}
+
+#[test]
+fn method_timeout_fails_the_call_when_the_handler_is_late() {
+    let (client, handle) = spawn_worker();
+    assert_eq!(Ok(0), client.slow(0));
+    assert_eq!(Err(ClientError::Timeout), client.slow(200));
+    drop(client);
+    handle.join().unwrap();
+}
+
+#[test]
+fn method_timeout_fails_result_calls_with_call_error() {
+    let (client, handle) = spawn_worker();
+    assert_eq!(Ok(3), client.parse("3".to_owned(), 0));
+    assert_eq!(
+        Err(CallError::Failed("three".to_owned())),
+        client.parse("three".to_owned(), 0)
+    );
+    assert_eq!(Err(CallError::Timeout), client.parse("3".to_owned(), 200));
+    drop(client);
+    handle.join().unwrap();
+}
+
+#[test]
+fn with_timeout_sets_the_timeout_of_a_single_call() {
+    let (client, handle) = spawn_worker();
+    let timeout = Duration::from_millis(20);
+    assert_eq!(Ok(0), client.with_timeout(timeout).sleep(0));
+    assert_eq!(
+        Err(ClientError::Timeout),
+        client.with_timeout(timeout).sleep(200)
+    );
+    // Other calls still wait for their reply.
+    assert_eq!(200, client.sleep(200));
+    // It overrides the timeout of the method.
+    assert_eq!(Ok(50), client.with_timeout(Duration::from_secs(5)).slow(50));
+    drop(client);
+    handle.join().unwrap();
+}
+
+#[test]
+fn timed_out_calls_report_their_dropped_reply() {
+    let (client, handle) = spawn_worker();
+    assert_eq!(Err(ClientError::Timeout), client.slow(200));
+    assert_eq!(Err(CallError::Timeout), client.parse("3".to_owned(), 200));
+    drop(client);
+    assert_eq!(vec!["slow", "parse"], handle.join().unwrap().dropped);
+}
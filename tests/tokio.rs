@@ -0,0 +1,87 @@
+use channel_protocol::{channel_actor, channel_protocol};
+use channel_protocol_runtime::ClientError;
+
+#[channel_protocol(tokio)]
+trait Journal {
+    fn write(entry: String);
+    fn entries() -> usize;
+}
+
+#[derive(Default)]
+struct State {
+    entries: Vec<String>,
+}
+
+impl HandleJournalAsync for State {
+    async fn write(&mut self, entry: String) {
+        tokio::task::yield_now().await;
+        self.entries.push(entry);
+    }
+
+    async fn entries(&mut self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    total: u32,
+}
+
+#[channel_actor(tokio)]
+impl Tally {
+    fn add(&mut self, n: u32) {
+        self.total += n;
+    }
+
+    fn total(&mut self) -> u32 {
+        self.total
+    }
+}
+
+#[tokio::test]
+async fn spawn_runs_the_handler_until_every_client_is_dropped() {
+    let (client, task) = JournalClient::spawn(State::default());
+    let other = client.clone();
+    client.write("first".to_owned()).await;
+    other.write("second".to_owned()).await;
+    assert_eq!(2, client.entries().await);
+
+    drop(client);
+    assert_eq!(2, other.entries().await);
+    drop(other);
+    assert_eq!(vec!["first", "second"], task.await.unwrap().entries);
+}
+
+#[tokio::test]
+async fn weak_clients_do_not_keep_the_task_running() {
+    let (client, task) = JournalClient::spawn(State::default());
+    let weak = client.downgrade();
+    weak.upgrade().unwrap().write("upgraded".to_owned()).await;
+    assert!(weak.is_connected());
+
+    drop(client);
+    assert_eq!(vec!["upgraded"], task.await.unwrap().entries);
+    assert!(!weak.is_connected());
+}
+
+#[tokio::test]
+async fn clients_see_the_end_of_the_task() {
+    let (client, task) = JournalClient::spawn(State::default());
+    assert!(client.is_connected());
+
+    task.abort();
+    assert!(task.await.is_err_and(|error| error.is_cancelled()));
+    assert!(!client.is_connected());
+    assert_eq!(Err(ClientError::Disconnected), client.try_entries().await);
+}
+
+#[tokio::test]
+async fn spawn_runs_an_actor() {
+    let (client, task) = TallyClient::spawn(Tally::default());
+    client.add(2).await;
+    client.add(3).await;
+    assert_eq!(5, client.total().await);
+    drop(client);
+    assert_eq!(5, task.await.unwrap().total);
+}
@@ -0,0 +1,11 @@
+#[test]
+fn compile_fail() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/compile-fail/*.rs");
+}
+
+#[test]
+fn pass() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/pass/*.rs");
+}
@@ -0,0 +1,91 @@
+use std::thread;
+
+use channel_protocol::channel_protocol;
+use channel_protocol_runtime::Dispatch;
+
+#[channel_protocol]
+trait Log {
+    fn push(line: String);
+    fn len() -> usize;
+}
+
+#[channel_protocol(sender = flume::Sender)]
+trait Ping {
+    fn ping();
+}
+
+#[derive(Default)]
+struct State {
+    lines: Vec<String>,
+}
+
+impl HandleLog for State {
+    fn push(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn len(&mut self) -> usize {
+        self.lines.len()
+    }
+}
+
+#[test]
+fn weak_clients_upgrade_while_a_client_is_alive() {
+    let (client, rx) = LogClient::new();
+    let weak = client.downgrade();
+    weak.upgrade().unwrap().push("upgraded".to_owned());
+    assert_eq!(1, rx.try_iter().count());
+
+    drop(client);
+    assert!(weak.upgrade().is_none());
+    assert!(!weak.is_connected());
+}
+
+#[test]
+fn weak_clients_do_not_keep_the_handler_running() {
+    let (client, rx) = LogClient::new();
+    let weak = client.downgrade();
+    let handle = thread::spawn(move || {
+        let mut state = State::default();
+        for message in rx {
+            state.dispatch(message);
+        }
+        state
+    });
+
+    client.push("first".to_owned());
+    assert_eq!(1, weak.upgrade().unwrap().len());
+    drop(client);
+    assert_eq!(vec!["first"], handle.join().unwrap().lines);
+}
+
+#[test]
+fn bounded_clients_see_a_dropped_receiver() {
+    let (client, rx) = LogClient::bounded(1);
+    let weak = client.downgrade();
+    assert!(client.is_connected());
+    assert!(weak.is_connected());
+
+    drop(rx);
+    assert!(!client.is_connected());
+    assert!(!weak.is_connected());
+}
+
+#[test]
+fn std_clients_cannot_see_a_dropped_receiver() {
+    let (client, rx) = LogClient::new();
+    drop(rx);
+    assert!(client.is_connected());
+    assert!(client.try_push("lost".to_owned()).is_err());
+}
+
+#[test]
+fn flume_clients_see_a_dropped_receiver() {
+    let (tx, rx) = flume::unbounded();
+    let client = PingClient::from_sender(tx);
+    assert!(client.is_connected());
+
+    drop(rx);
+    assert!(!client.is_connected());
+    assert!(!client.downgrade().is_connected());
+}